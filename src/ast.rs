@@ -1,32 +1,106 @@
 use crate::common::Span;
 use std::rc::Rc;
 
+#[derive(Debug, Clone, Copy)]
+pub enum Operator {
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+impl std::fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::Modulo => "%",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Comparison {
+    Equals,
+    NotEquals,
+    LessThan,
+    GreaterThan,
+    LessEquals,
+    GreaterEquals,
+    In,
+    NotIn,
+}
+
+impl std::fmt::Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Comparison::Equals => "==",
+            Comparison::NotEquals => "!=",
+            Comparison::LessThan => "<",
+            Comparison::GreaterThan => ">",
+            Comparison::LessEquals => "<=",
+            Comparison::GreaterEquals => ">=",
+            Comparison::In => "in",
+            Comparison::NotIn => "not in",
+        })
+    }
+}
+
 #[derive(Debug)]
 pub enum AST {
     And(Span, Rc<AST>, Rc<AST>),
-    Assert(Span, Rc<AST>),
+    /// Condition, and the optional message shown when it fails
+    Assert(Span, Rc<AST>, Option<String>),
     Assignment(Span, Rc<AST>, Rc<AST>),
+    BitAnd(Span, Rc<AST>, Rc<AST>),
+    BitNot(Span, Rc<AST>),
+    BitOr(Span, Rc<AST>, Rc<AST>),
+    BitXor(Span, Rc<AST>, Rc<AST>),
     Block(Span, Vec<Rc<AST>>),
     BooleanLiteral(Span, bool),
-    Call(Span, Rc<AST>, Vec<Rc<AST>>),
+    /// Callee, positional arguments, and `name = value` keyword arguments
+    Call(Span, Rc<AST>, Vec<Rc<AST>>, Vec<(String, Rc<AST>)>),
+    /// `a < b < c`, which means `a < b and b < c` with `b` evaluated once
+    ChainedComparison(Span, Rc<AST>, Vec<(Comparison, Rc<AST>)>),
+    CompoundAssignment(Span, Operator, Rc<AST>, Rc<AST>),
+    Del(Span, Rc<AST>),
     Divide(Span, Rc<AST>, Rc<AST>),
     FloatLiteral(Span, f64),
+    FloorDivide(Span, Rc<AST>, Rc<AST>),
+    FormatString(Span, Vec<Rc<AST>>),
     Function {
         span: Span,
         name: Option<String>,
         args: Vec<String>,
+        /// Name of a trailing `*args` parameter, if any
+        variadic: Option<String>,
         body: Rc<AST>,
     },
     If(Span, Rc<AST>, Rc<AST>, Option<Rc<AST>>),
     Index(Span, Rc<AST>, Rc<AST>),
     IntegerLiteral(Span, i64),
+    /// Scrutinee, `pattern => body` arms, and the optional `else` arm
+    Match(Span, Rc<AST>, Vec<(Rc<AST>, Rc<AST>)>, Option<Rc<AST>>),
+    Member(Span, Rc<AST>, String),
     Minus(Span, Rc<AST>, Rc<AST>),
+    Modulo(Span, Rc<AST>, Rc<AST>),
+    /// `a, b = x, y`, or `a, b = pair` to unpack a single value.
+    MultiAssignment(Span, Vec<Rc<AST>>, Vec<Rc<AST>>),
+    /// `let a, b = x, y`, or `let [a, b] = pair` to unpack a single value.
+    MultiDeclaration(Span, Vec<String>, Vec<Rc<AST>>),
     Multiply(Span, Rc<AST>, Rc<AST>),
+    Negate(Span, Rc<AST>),
     Not(Span, Rc<AST>),
     Nothing(Span),
     Or(Span, Rc<AST>, Rc<AST>),
     Plus(Span, Rc<AST>, Rc<AST>),
+    Power(Span, Rc<AST>, Rc<AST>),
     Return(Span, Rc<AST>),
+    ShiftLeft(Span, Rc<AST>, Rc<AST>),
+    ShiftRight(Span, Rc<AST>, Rc<AST>),
     Slice {
         span: Span,
         lhs: Rc<AST>,
@@ -39,6 +113,8 @@ pub enum AST {
     Variable(Span, String),
     Equals(Span, Rc<AST>, Rc<AST>),
     NotEquals(Span, Rc<AST>, Rc<AST>),
+    In(Span, Rc<AST>, Rc<AST>),
+    NotIn(Span, Rc<AST>, Rc<AST>),
     LessThan(Span, Rc<AST>, Rc<AST>),
     GreaterThan(Span, Rc<AST>, Rc<AST>),
     LessEquals(Span, Rc<AST>, Rc<AST>),
@@ -67,28 +143,48 @@ impl AST {
             AST::And(span, ..) => span,
             AST::Assert(span, ..) => span,
             AST::Assignment(span, ..) => span,
+            AST::BitAnd(span, ..) => span,
+            AST::BitNot(span, ..) => span,
+            AST::BitOr(span, ..) => span,
+            AST::BitXor(span, ..) => span,
             AST::Block(span, ..) => span,
             AST::BooleanLiteral(span, ..) => span,
             AST::Call(span, ..) => span,
+            AST::ChainedComparison(span, ..) => span,
+            AST::CompoundAssignment(span, ..) => span,
+            AST::Del(span, ..) => span,
             AST::Divide(span, ..) => span,
+            AST::FloorDivide(span, ..) => span,
             AST::FloatLiteral(span, ..) => span,
+            AST::FormatString(span, ..) => span,
             AST::Function { span, .. } => span,
             AST::If(span, ..) => span,
+            AST::Match(span, ..) => span,
             AST::Index(span, ..) => span,
             AST::IntegerLiteral(span, ..) => span,
+            AST::Member(span, ..) => span,
             AST::Minus(span, ..) => span,
+            AST::Modulo(span, ..) => span,
+            AST::MultiAssignment(span, ..) => span,
+            AST::MultiDeclaration(span, ..) => span,
             AST::Multiply(span, ..) => span,
+            AST::Negate(span, ..) => span,
             AST::Not(span, ..) => span,
             AST::Nothing(span, ..) => span,
             AST::Or(span, ..) => span,
             AST::Plus(span, ..) => span,
+            AST::Power(span, ..) => span,
             AST::Return(span, ..) => span,
+            AST::ShiftLeft(span, ..) => span,
+            AST::ShiftRight(span, ..) => span,
             AST::Slice { span, .. } => span,
             AST::StringLiteral(span, ..) => span,
             AST::VarDeclaration(span, ..) => span,
             AST::Variable(span, ..) => span,
             AST::Equals(span, ..) => span,
             AST::NotEquals(span, ..) => span,
+            AST::In(span, ..) => span,
+            AST::NotIn(span, ..) => span,
             AST::LessThan(span, ..) => span,
             AST::GreaterThan(span, ..) => span,
             AST::LessEquals(span, ..) => span,
@@ -110,11 +206,16 @@ impl std::fmt::Display for AST {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             AST::And(_, lhs, rhs) => write!(f, "({} and {})", lhs, rhs),
-            AST::Assert(_, expr) => write!(f, "assert {}", expr),
+            AST::Assert(_, expr, None) => write!(f, "assert {}", expr),
+            AST::Assert(_, expr, Some(message)) => write!(f, "assert {}, \"{}\"", expr, message),
             AST::Assignment(_, lhs, rhs) => write!(f, "{} = {}", lhs, rhs),
+            AST::BitAnd(_, lhs, rhs) => write!(f, "({} & {})", lhs, rhs),
+            AST::BitNot(_, expr) => write!(f, "~{}", expr),
+            AST::BitOr(_, lhs, rhs) => write!(f, "({} | {})", lhs, rhs),
+            AST::BitXor(_, lhs, rhs) => write!(f, "({} ^ {})", lhs, rhs),
             AST::Block(_, exprs) => write!(f, "<block with {} exprs>", exprs.len()),
             AST::BooleanLiteral(_, val) => write!(f, "{}", val),
-            AST::Call(_, func, args) => {
+            AST::Call(_, func, args, kwargs) => {
                 write!(f, "{}(", func)?;
                 for (i, arg) in args.iter().enumerate() {
                     if i > 0 {
@@ -122,25 +223,69 @@ impl std::fmt::Display for AST {
                     }
                     write!(f, "{}", arg)?;
                 }
+                for (i, (name, arg)) in kwargs.iter().enumerate() {
+                    if i > 0 || !args.is_empty() {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}={}", name, arg)?;
+                }
+                write!(f, ")")
+            }
+            AST::ChainedComparison(_, first, rest) => {
+                write!(f, "({}", first)?;
+                for (op, expr) in rest {
+                    write!(f, " {} {}", op, expr)?;
+                }
                 write!(f, ")")
             }
+            AST::CompoundAssignment(_, op, lhs, rhs) => write!(f, "{} {}= {}", lhs, op, rhs),
+            AST::Del(_, target) => write!(f, "del {}", target),
             AST::Divide(_, lhs, rhs) => write!(f, "({} / {})", lhs, rhs),
+            AST::FloorDivide(_, lhs, rhs) => write!(f, "({} // {})", lhs, rhs),
             AST::FloatLiteral(_, val) => write!(f, "{}", val),
+            AST::FormatString(_, parts) => {
+                write!(f, "f\"")?;
+                for part in parts {
+                    match part.as_ref() {
+                        AST::StringLiteral(_, text) => {
+                            write!(f, "{}", text.replace('{', "{{").replace('}', "}}"))?
+                        }
+                        expr => write!(f, "{{{}}}", expr)?,
+                    }
+                }
+                write!(f, "\"")
+            }
             AST::Function { name, .. } => write!(
                 f,
                 "def {} => ...",
                 name.clone().unwrap_or_else(|| "<anon>".to_string())
             ),
             AST::If(_, cond, ..) => write!(f, "if {}", cond),
+            AST::Match(_, value, ..) => write!(f, "match {}", value),
             AST::Index(_, lhs, rhs) => write!(f, "{}[{}]", lhs, rhs),
             AST::IntegerLiteral(_, val) => write!(f, "{}", val),
+            AST::Member(_, lhs, name) => write!(f, "{}.{}", lhs, name),
             AST::Minus(_, lhs, rhs) => write!(f, "({} - {})", lhs, rhs),
+            AST::Modulo(_, lhs, rhs) => write!(f, "({} % {})", lhs, rhs),
+            AST::MultiAssignment(_, targets, values) => {
+                let targets = targets.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+                let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+                write!(f, "{} = {}", targets.join(", "), values.join(", "))
+            }
+            AST::MultiDeclaration(_, names, values) => {
+                let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+                write!(f, "let [{}] = {}", names.join(", "), values.join(", "))
+            }
             AST::Multiply(_, lhs, rhs) => write!(f, "({} * {})", lhs, rhs),
+            AST::Negate(_, expr) => write!(f, "-{}", expr),
             AST::Not(_, expr) => write!(f, "not {}", expr),
             AST::Nothing(_) => write!(f, "nothing"),
             AST::Or(_, lhs, rhs) => write!(f, "({} or {})", lhs, rhs),
             AST::Plus(_, lhs, rhs) => write!(f, "({} + {})", lhs, rhs),
+            AST::Power(_, lhs, rhs) => write!(f, "({} ** {})", lhs, rhs),
             AST::Return(_, expr) => write!(f, "return {}", expr),
+            AST::ShiftLeft(_, lhs, rhs) => write!(f, "({} << {})", lhs, rhs),
+            AST::ShiftRight(_, lhs, rhs) => write!(f, "({} >> {})", lhs, rhs),
             AST::Slice {
                 lhs,
                 start,
@@ -166,6 +311,8 @@ impl std::fmt::Display for AST {
             AST::Variable(_, name) => write!(f, "{}", name),
             AST::Equals(_, lhs, rhs) => write!(f, "({} == {})", lhs, rhs),
             AST::NotEquals(_, lhs, rhs) => write!(f, "({} != {})", lhs, rhs),
+            AST::In(_, lhs, rhs) => write!(f, "({} in {})", lhs, rhs),
+            AST::NotIn(_, lhs, rhs) => write!(f, "({} not in {})", lhs, rhs),
             AST::LessThan(_, lhs, rhs) => write!(f, "({} < {})", lhs, rhs),
             AST::GreaterThan(_, lhs, rhs) => write!(f, "({} > {})", lhs, rhs),
             AST::LessEquals(_, lhs, rhs) => write!(f, "({} <= {})", lhs, rhs),
@@ -17,10 +17,17 @@ macro_rules! make {
 }
 pub(crate) use make;
 
+/// Tabs advance the column to the next multiple of this, like most editors.
+pub const DEFAULT_TAB_WIDTH: usize = 8;
+
 #[derive(Clone, Copy)]
 pub struct Location {
     pub line: usize,
+    /// Display column, with tabs expanded to the next tab stop
     pub column: usize,
+    /// Column counting every character (tabs included) as one, used to
+    /// find the location in the source text
+    pub char_column: usize,
     pub filename: &'static str,
 }
 
@@ -56,4 +63,28 @@ impl Span {
     pub fn extend(&self, other: &Span) -> Span {
         Span(self.0, other.1)
     }
+
+    /// The source text covered by this span, if its file can be read.
+    pub fn source_text(&self) -> Option<String> {
+        let content = std::fs::read_to_string(self.0.filename).ok()?;
+        let lines = content
+            .lines()
+            .skip(self.0.line - 1)
+            .take(self.1.line - self.0.line + 1)
+            .collect::<Vec<_>>();
+        let mut text = String::new();
+        for (i, line) in lines.iter().enumerate() {
+            let start = if i == 0 { self.0.char_column - 1 } else { 0 };
+            let end = if i == lines.len() - 1 {
+                self.1.char_column - 1
+            } else {
+                usize::MAX
+            };
+            if i > 0 {
+                text.push('\n');
+            }
+            text.extend(line.chars().take(end).skip(start));
+        }
+        Some(text)
+    }
 }
@@ -6,6 +6,19 @@ pub enum ErrorKind {
     Parser,
     UnexpectedEOF,
     Runtime,
+    Abort,
+    /// Reading a source file failed; the span only names the file.
+    IO,
+}
+
+/// A function call that was active when an error was raised.
+#[derive(Debug)]
+pub struct Frame {
+    pub name: String,
+    pub span: Span,
+    /// Parameter names with the repr of their values, captured only once
+    /// the error reaches this frame.
+    pub args: Vec<(String, String)>,
 }
 
 #[derive(Debug)]
@@ -13,6 +26,8 @@ pub struct Error {
     pub kind: ErrorKind,
     pub span: Span,
     pub message: String,
+    /// Innermost call first, filled in as the error propagates.
+    pub trace: Vec<Frame>,
 }
 
 impl std::fmt::Display for Error {
@@ -22,6 +37,8 @@ impl std::fmt::Display for Error {
                 write!(f, "SyntaxError: {}", self.message)
             }
             ErrorKind::Runtime => write!(f, "RuntimeError: {}", self.message),
+            ErrorKind::Abort => write!(f, "Aborted: {}", self.message),
+            ErrorKind::IO => write!(f, "IOError: {}", self.message),
         }
     }
 }
@@ -34,6 +51,7 @@ macro_rules! lexer_error {
             kind: crate::error::ErrorKind::Lexer,
             span: $span.clone(),
             message: format!($($arg)*),
+            trace: vec![],
         })
     }
 }
@@ -45,6 +63,7 @@ macro_rules! parser_error {
             kind: crate::error::ErrorKind::Parser,
             span: $span.clone(),
             message: format!($($arg)*),
+            trace: vec![],
         })
     }
 }
@@ -56,6 +75,7 @@ macro_rules! eof_error {
             kind: crate::error::ErrorKind::UnexpectedEOF,
             span: $span.clone(),
             message: format!("Unexpected EOF: {}", format!($($arg)*)),
+            trace: vec![],
         })
     }
 }
@@ -67,6 +87,7 @@ macro_rules! runtime_error {
             kind: crate::error::ErrorKind::Runtime,
             span: $span.clone(),
             message: format!($($arg)*),
+            trace: vec![],
         })
     }
 }
@@ -74,13 +95,42 @@ pub(crate) use runtime_error;
 
 
 impl Error {
-    pub fn print_with_source(&self) {
+    pub fn format_trace(&self) -> String {
+        let mut res = String::new();
+        if !self.trace.is_empty() {
+            res.push_str("Traceback (most recent call last):\n");
+        }
+        for frame in self.trace.iter().rev() {
+            let args = frame
+                .args
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<_>>()
+                .join(", ");
+            res.push_str(&format!("  {}({}) called at {}\n", frame.name, args, frame.span.0));
+        }
+        res
+    }
+
+    /// Report an `abort()` from user code: flush whatever the program has
+    /// printed so far, then print the message and trace to stderr.
+    pub fn print_abort(&self) {
+        use std::io::Write;
+        std::io::stdout().flush().expect("Failed to flush stdout");
+        eprintln!("{}: {}", self.span.0, self);
+        eprint!("{}", self.format_trace());
+    }
+
+    /// `tab_width` must match the one the source was lexed with, so that
+    /// the highlighted columns line up with the expanded tabs.
+    pub fn print_with_source(&self, tab_width: usize) {
         let msg = &self.message;
         let filename = &self.span.0.filename;
         let file_content = match std::fs::read_to_string(filename) {
             Ok(content) => content,
             Err(_) => {
                 println!("{}: Error: {}", self.span.0, msg);
+                print!("{}", self.format_trace());
                 return;
             }
         };
@@ -90,19 +140,16 @@ impl Error {
         let start = self.span.0;
         let end = self.span.1;
 
-        let min_line = if start.line <= context {
-            1
-        } else {
-            start.line - context - 1
-        };
+        // `line_no` below is 0-based, while locations count lines from 1
+        let min_line = start.line.saturating_sub(context + 1);
         let max_line = lines.len().min(end.line + context);
 
         println!("╭────────────────────────────────────────────────────────────────────────────────");
         println!("│ {}: Error: {}", start, msg);
         println!("├─────┬──────────────────────────────────────────────────────────────────────────");
 
-        for line_no in min_line..max_line {
-            let line = lines[line_no];
+        for (line_no, line) in lines.iter().enumerate().take(max_line).skip(min_line) {
+            let line = expand_tabs(line, tab_width);
             if start.line - 1 <= line_no && line_no < end.line {
                 let highlight_start = if line_no == start.line - 1 {
                     start.column - 1
@@ -115,23 +162,43 @@ impl Error {
                     line.len()
                 };
 
-                let text_before = &line[..highlight_start];
-                let text_highlight = &line[highlight_start..highlight_end];
-                let text_after = &line[highlight_end..];
-                println!("│ {:>3} │ {}\x1b[0;31m{}\x1b[0m{}", line_no, text_before, text_highlight, text_after);
+                let highlight_end = highlight_end.min(line.len());
+                let highlight_start = highlight_start.min(highlight_end);
+
+                let text_before = line[..highlight_start].iter().collect::<String>();
+                let text_highlight = line[highlight_start..highlight_end].iter().collect::<String>();
+                let text_after = line[highlight_end..].iter().collect::<String>();
+                println!("│ {:>3} │ {}\x1b[0;31m{}\x1b[0m{}", line_no + 1, text_before, text_highlight, text_after);
 
                 if start.line == end.line {
-                    if text_highlight.len() <= 1 {
-                        println!("│     │ {}\x1b[0;31m▲\x1b[0m", " ".repeat(text_before.len()));
+                    if highlight_end - highlight_start <= 1 {
+                        println!("│     │ {}\x1b[0;31m▲\x1b[0m", " ".repeat(highlight_start));
                     } else {
-                        println!("│     │ {}\x1b[0;31m└{}┘\x1b[0m", " ".repeat(text_before.len()), "─".repeat(text_highlight.len()-2));
+                        println!("│     │ {}\x1b[0;31m└{}┘\x1b[0m", " ".repeat(highlight_start), "─".repeat(highlight_end - highlight_start - 2));
                     }
                 }
             } else {
-                println!("│ {:>3} │ {}", line_no, line);
+                println!("│ {:>3} │ {}", line_no + 1, line.iter().collect::<String>());
             }
         }
 
         println!("╰─────┴──────────────────────────────────────────────────────────────────────────");
+        print!("{}", self.format_trace());
+    }
+}
+
+// Lay the line out the same way the lexer counts columns, so that a column
+// is an index into the result.
+fn expand_tabs(line: &str, tab_width: usize) -> Vec<char> {
+    let mut res = vec![];
+    for c in line.chars() {
+        match c {
+            '\t' => {
+                let width = tab_width.max(1);
+                res.resize((res.len() / width + 1) * width, ' ');
+            }
+            _ => res.push(c),
+        }
     }
+    res
 }
@@ -1,15 +1,81 @@
-use crate::error::{runtime_error as error, Result};
-use crate::interpreter::value::Value;
-use crate::common::Span;
+use crate::error::{runtime_error as error, Error, ErrorKind, Result};
+use crate::interpreter::value::{range_items, range_len, IteratorValue, Value};
+use crate::interpreter::Interpreter;
+use crate::common::{get, make, Span};
+
+fn joined(args: &[Value], sep: &str) -> String {
+    args.iter().map(|arg| arg.repr()).collect::<Vec<_>>().join(sep)
+}
 
 pub fn print(_span: &Span, args: Vec<Value>) -> Result<Value> {
-    for (i, arg) in args.iter().enumerate() {
-        if i != 0 {
-            print!(" ");
+    println!("{}", joined(&args, " "));
+    Ok(Value::Nothing)
+}
+
+/// Like `print`, but joins the arguments with the given separator.
+pub fn print_sep(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.split_first() {
+        Some((Value::String(sep), args)) => println!("{}", joined(args, &sep.borrow())),
+        _ => error!(span, "print_sep() expects a string separator as its first argument"),
+    }
+    Ok(Value::Nothing)
+}
+
+/// Like `print`, but without the trailing newline.
+pub fn write(_span: &Span, args: Vec<Value>) -> Result<Value> {
+    print!("{}", joined(&args, " "));
+    Ok(Value::Nothing)
+}
+
+/// Replaces each `{}` in the template with the next argument, stringified
+/// the same way as in format strings. `{{` and `}}` are literal braces.
+fn substitute(span: &Span, name: &str, args: Vec<Value>) -> Result<String> {
+    let (template, args) = match args.split_first() {
+        Some((Value::String(template), args)) => (template.borrow().clone(), args.to_vec()),
+        _ => error!(span, "{}() expects a string template as its first argument", name),
+    };
+
+    let given = args.len();
+    let mut result = String::new();
+    let mut args = args.iter();
+    let mut placeholders = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                result.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                placeholders += 1;
+                if let Some(arg) = args.next() {
+                    result.push_str(&arg.to_string());
+                }
+            }
+            ('{', _) | ('}', _) => {
+                error!(span, "{}() only supports `{{}}` placeholders, use `{{{{` or `}}}}` for braces", name)
+            }
+            _ => result.push(c),
         }
-        print!("{}", arg.repr())
     }
-    println!();
+
+    if placeholders != given {
+        error!(
+            span,
+            "{}() template has {} placeholders, but got {} arguments", name, placeholders, given
+        )
+    }
+    Ok(result)
+}
+
+pub fn format(span: &Span, args: Vec<Value>) -> Result<Value> {
+    Ok(Value::String(make!(substitute(span, "format", args)?)))
+}
+
+/// Like `format`, but prints the result without a trailing newline.
+pub fn printf(span: &Span, args: Vec<Value>) -> Result<Value> {
+    print!("{}", substitute(span, "printf", args)?);
     Ok(Value::Nothing)
 }
 
@@ -18,16 +84,71 @@ pub fn len(span: &Span, args: Vec<Value>) -> Result<Value> {
         error!(span, "len() takes exactly one argument");
     }
 
-    Ok(match &args[0] {
-        Value::String(string) => Value::Integer(string.borrow().len() as i64),
-        Value::Array(array) => Value::Integer(array.borrow().len() as i64),
-        Value::Range(start, end) => Value::Integer(end - start),
-        other => error!(span, "len() does not support {:?}", other),
+    match args[0].length() {
+        Some(len) => Ok(Value::Integer(len)),
+        None => error!(span, "len() does not support {:?}", args[0]),
+    }
+}
+
+/// Strings are returned as they are, everything else as it would be printed.
+pub fn str(span: &Span, args: Vec<Value>) -> Result<Value> {
+    if args.len() != 1 {
+        error!(span, "str() takes exactly one argument");
+    }
+    Ok(Value::String(make!(args[0].to_string())))
+}
+
+/// Floats are truncated toward zero; strings must hold a decimal integer.
+pub fn int(span: &Span, args: Vec<Value>) -> Result<Value> {
+    Ok(match args.as_slice() {
+        [Value::Integer(i)] => Value::Integer(*i),
+        [Value::Boolean(b)] => Value::Integer(*b as i64),
+        [Value::Float(f)] => float_to_int(f.trunc(), "int", span)?,
+        [Value::String(s)] => match s.borrow().trim().parse::<i64>() {
+            Ok(i) => Value::Integer(i),
+            Err(_) => error!(span, "int() can't parse {:?} as an integer", s.borrow()),
+        },
+        [other] => error!(span, "int() can't convert {:?} to an integer", other),
+        _ => error!(span, "int() takes exactly one argument"),
+    })
+}
+
+pub fn float(span: &Span, args: Vec<Value>) -> Result<Value> {
+    Ok(match args.as_slice() {
+        [Value::Float(f)] => Value::Float(*f),
+        [Value::Integer(i)] => Value::Float(*i as f64),
+        [Value::String(s)] => match s.borrow().trim().parse::<f64>() {
+            Ok(f) => Value::Float(f),
+            Err(_) => error!(span, "float() can't parse {:?} as a float", s.borrow()),
+        },
+        [other] => error!(span, "float() can't convert {:?} to a float", other),
+        _ => error!(span, "float() takes exactly one argument"),
     })
 }
 
+pub fn r#type(span: &Span, args: Vec<Value>) -> Result<Value> {
+    if args.len() != 1 {
+        error!(span, "type() takes exactly one argument");
+    }
+    Ok(Value::String(make!(args[0].type_name().to_string())))
+}
+
+/// `range(end)`, `range(start, end)` or `range(start, end, step)`.
+pub fn range(span: &Span, args: Vec<Value>) -> Result<Value> {
+    let (start, end, step) = match args.as_slice() {
+        [Value::Integer(end)] => (0, *end, 1),
+        [Value::Integer(start), Value::Integer(end)] => (*start, *end, 1),
+        [Value::Integer(start), Value::Integer(end), Value::Integer(step)] => (*start, *end, *step),
+        _ => error!(span, "range() expects one to three integers"),
+    };
+    if step == 0 {
+        error!(span, "range() step cannot be 0");
+    }
+    Ok(Value::Range(start, end, step))
+}
+
 pub fn exit(span: &Span, args: Vec<Value>) -> Result<Value> {
-    let code = match args.get(0) {
+    let code = match args.first() {
         Some(val) => match val {
             Value::Integer(i) => *i,
             _ => error!(span, "exit() may only take an integer as argument"),
@@ -40,3 +161,791 @@ pub fn exit(span: &Span, args: Vec<Value>) -> Result<Value> {
         Err(_) => std::process::exit(1),
     }
 }
+
+pub fn list(span: &Span, args: Vec<Value>) -> Result<Value> {
+    if args.len() != 1 {
+        error!(span, "list() takes exactly one argument");
+    }
+    Ok(Value::Array(make!(args[0].iterator(span)?.collect())))
+}
+
+pub fn take(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [iter, Value::Integer(n)] => Ok(Value::Iterator(iter.iterator(span)?.take(*n))),
+        _ => error!(span, "take() expects an iterable and an integer"),
+    }
+}
+
+pub fn skip(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [iter, Value::Integer(n)] => Ok(Value::Iterator(iter.iterator(span)?.skip(*n))),
+        _ => error!(span, "skip() expects an iterable and an integer"),
+    }
+}
+
+pub fn chain(span: &Span, args: Vec<Value>) -> Result<Value> {
+    let iters = args
+        .iter()
+        .map(|arg| arg.iterator(span))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Value::Iterator(IteratorValue::chain(iters)))
+}
+
+pub fn enumerate(span: &Span, args: Vec<Value>) -> Result<Value> {
+    if args.len() != 1 {
+        error!(span, "enumerate() takes exactly one argument");
+    }
+    Ok(Value::Iterator(args[0].iterator(span)?.enumerate()))
+}
+
+/// Formats an integer with `sep` (default `,`) between groups of three
+/// digits. Floats are rejected rather than silently truncated.
+pub fn group_digits(span: &Span, args: Vec<Value>) -> Result<Value> {
+    let (num, sep) = match args.as_slice() {
+        [Value::Integer(num)] => (*num, ",".to_string()),
+        [Value::Integer(num), Value::String(sep)] => (*num, sep.borrow().clone()),
+        _ => error!(span, "group_digits() expects an integer and an optional separator string"),
+    };
+
+    let digits = num.unsigned_abs().to_string();
+    let mut res = String::new();
+    if num < 0 {
+        res.push('-');
+    }
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            res.push_str(&sep);
+        }
+        res.push(c);
+    }
+    Ok(Value::String(make!(res)))
+}
+
+fn as_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(i) => Some(*i as f64),
+        Value::Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
+fn float_to_int(f: f64, name: &str, span: &Span) -> Result<Value> {
+    if !f.is_finite() || f < i64::MIN as f64 || f >= i64::MAX as f64 {
+        error!(span, "{}() can't convert {} to an integer", name, f)
+    }
+    Ok(Value::Integer(f as i64))
+}
+
+pub fn sqrt(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [value] => match as_float(value) {
+            Some(f) if f < 0.0 => error!(span, "sqrt() of negative number {}", f),
+            Some(f) => Ok(Value::Float(f.sqrt())),
+            None => error!(span, "sqrt() expects a number, got {:?}", value),
+        },
+        _ => error!(span, "sqrt() takes exactly one argument"),
+    }
+}
+
+// `floor`, `ceil` and `round` return integers, so they can be used as indices
+fn to_integer(span: &Span, args: Vec<Value>, name: &str, op: fn(f64) -> f64) -> Result<Value> {
+    match args.as_slice() {
+        [Value::Integer(i)] => Ok(Value::Integer(*i)),
+        [Value::Float(f)] => float_to_int(op(*f), name, span),
+        [other] => error!(span, "{}() expects a number, got {:?}", name, other),
+        _ => error!(span, "{}() takes exactly one argument", name),
+    }
+}
+
+pub fn floor(span: &Span, args: Vec<Value>) -> Result<Value> {
+    to_integer(span, args, "floor", f64::floor)
+}
+
+pub fn ceil(span: &Span, args: Vec<Value>) -> Result<Value> {
+    to_integer(span, args, "ceil", f64::ceil)
+}
+
+/// Rounds half away from zero.
+pub fn round(span: &Span, args: Vec<Value>) -> Result<Value> {
+    to_integer(span, args, "round", f64::round)
+}
+
+/// Always returns a float; use `**` for exact integer powers.
+pub fn pow(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [base, exp] => match (as_float(base), as_float(exp)) {
+            (Some(base), Some(exp)) => Ok(Value::Float(base.powf(exp))),
+            _ => error!(span, "pow() expects two numbers"),
+        },
+        _ => error!(span, "pow() takes exactly two arguments"),
+    }
+}
+
+/// Whether two numbers are within `eps` (default 1e-9) of each other.
+pub fn approx_eq(span: &Span, args: Vec<Value>) -> Result<Value> {
+    let nums = args.iter().map(as_float).collect::<Option<Vec<_>>>();
+    let (a, b, eps) = match nums.as_deref() {
+        Some([a, b]) => (*a, *b, 1e-9),
+        Some([a, b, eps]) => (*a, *b, *eps),
+        _ => error!(span, "approx_eq() expects two numbers and an optional epsilon"),
+    };
+    Ok(Value::Boolean((a - b).abs() <= eps))
+}
+
+pub fn upper(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [Value::String(string)] => Ok(Value::String(make!(string.borrow().to_uppercase()))),
+        _ => error!(span, "upper() takes exactly one string argument"),
+    }
+}
+
+pub fn lower(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [Value::String(string)] => Ok(Value::String(make!(string.borrow().to_lowercase()))),
+        _ => error!(span, "lower() takes exactly one string argument"),
+    }
+}
+
+pub fn strip(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [Value::String(string)] => Ok(Value::String(make!(string.borrow().trim().to_string()))),
+        _ => error!(span, "strip() takes exactly one string argument"),
+    }
+}
+
+/// Without a separator, splits on runs of whitespace and drops empty parts.
+pub fn split(span: &Span, args: Vec<Value>) -> Result<Value> {
+    let to_values = |parts: Vec<&str>| {
+        let parts = parts.into_iter().map(|part| Value::String(make!(part.to_string())));
+        Value::Array(make!(parts.collect()))
+    };
+    match args.as_slice() {
+        [Value::String(string)] => Ok(to_values(string.borrow().split_whitespace().collect())),
+        [Value::String(string), Value::String(sep)] => {
+            if sep.borrow().is_empty() {
+                error!(span, "split() separator cannot be empty");
+            }
+            Ok(to_values(string.borrow().split(get!(sep)).collect()))
+        }
+        _ => error!(span, "split() expects a string and an optional separator"),
+    }
+}
+
+pub fn join(span: &Span, args: Vec<Value>) -> Result<Value> {
+    let (sep, iterable) = match args.as_slice() {
+        [Value::String(sep), iterable] => (sep, iterable),
+        _ => error!(span, "join() expects a separator string and an iterable"),
+    };
+    let mut parts = vec![];
+    for item in iterable.iterator(span)? {
+        match item {
+            Value::String(s) => parts.push(s.borrow().clone()),
+            other => error!(span, "join() can only join strings, got {}", other.type_name()),
+        }
+    }
+    Ok(Value::String(make!(parts.join(get!(sep)))))
+}
+
+pub fn ord(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [Value::String(string)] => {
+            let string = string.borrow();
+            let mut chars = string.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(Value::Integer(c as i64)),
+                _ => error!(span, "ord() expects a single character, got {:?}", *string),
+            }
+        }
+        _ => error!(span, "ord() takes exactly one string argument"),
+    }
+}
+
+pub fn chr(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [Value::Integer(code)] => match u32::try_from(*code).ok().and_then(char::from_u32) {
+            Some(c) => Ok(Value::String(make!(c.to_string()))),
+            None => error!(span, "chr() got an invalid code point {}", code),
+        },
+        _ => error!(span, "chr() takes exactly one integer argument"),
+    }
+}
+
+pub fn capitalize(span: &Span, args: Vec<Value>) -> Result<Value> {
+    let string = match args.as_slice() {
+        [Value::String(string)] => string.borrow(),
+        _ => error!(span, "capitalize() takes exactly one string argument"),
+    };
+    let mut chars = string.chars();
+    let res = match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    };
+    Ok(Value::String(make!(res)))
+}
+
+pub fn title(span: &Span, args: Vec<Value>) -> Result<Value> {
+    let string = match args.as_slice() {
+        [Value::String(string)] => string.borrow(),
+        _ => error!(span, "title() takes exactly one string argument"),
+    };
+    let mut res = String::new();
+    let mut word_start = true;
+    for c in string.chars() {
+        if c.is_whitespace() {
+            res.push(c);
+            word_start = true;
+        } else if word_start {
+            res.extend(c.to_uppercase());
+            word_start = false;
+        } else {
+            res.push(c);
+        }
+    }
+    Ok(Value::String(make!(res)))
+}
+
+/// Prints the source of the argument expression along with its value to
+/// stderr and returns the value, so it can be wrapped around any expression.
+pub fn dbg(span: &Span, args: Vec<Value>) -> Result<Value> {
+    if args.len() != 1 {
+        error!(span, "dbg() takes exactly one argument");
+    }
+    let value = args.into_iter().next().unwrap();
+    match dbg_label(span) {
+        Some(label) => eprintln!("[{}] {} = {}", span.0, label, value.repr()),
+        None => eprintln!("[{}] {}", span.0, value.repr()),
+    }
+    Ok(value)
+}
+
+/// The call span covers `dbg(<expr>)`, so the label is whatever is
+/// between the first `(` and the closing `)`.
+fn dbg_label(span: &Span) -> Option<String> {
+    let text = span.source_text()?;
+    let (_, rest) = text.split_once('(')?;
+    Some(rest.strip_suffix(')')?.trim().to_string())
+}
+
+pub fn product(span: &Span, args: Vec<Value>) -> Result<Value> {
+    if args.len() != 1 {
+        error!(span, "product() takes exactly one argument");
+    }
+
+    if let Value::Range(start, end, step) = &args[0] {
+        let mut acc: i64 = 1;
+        for i in range_items(*start, *end, *step) {
+            match acc.checked_mul(i) {
+                Some(0) => return Ok(Value::Integer(0)),
+                Some(res) => acc = res,
+                None => error!(span, "Integer overflow in product()"),
+            }
+        }
+        return Ok(Value::Integer(acc));
+    }
+
+    let mut acc = Value::Integer(1);
+    for item in args[0].iterator(span)? {
+        acc = match (&acc, &item) {
+            (Value::Integer(a), Value::Integer(b)) => match a.checked_mul(*b) {
+                Some(res) => Value::Integer(res),
+                None => error!(span, "Integer overflow in product()"),
+            },
+            (_, Value::Integer(_) | Value::Float(_)) => acc.multiply(&item, span)?,
+            _ => error!(span, "product() can only multiply numbers, got {}", item.type_name()),
+        }
+    }
+    Ok(acc)
+}
+
+pub fn abs(span: &Span, args: Vec<Value>) -> Result<Value> {
+    Ok(match args.as_slice() {
+        [Value::Integer(i)] => match i.checked_abs() {
+            Some(res) => Value::Integer(res),
+            None => error!(span, "Integer overflow in abs()"),
+        },
+        [Value::Float(f)] => Value::Float(f.abs()),
+        [other] => error!(span, "abs() expects a number, got {:?}", other),
+        _ => error!(span, "abs() takes exactly one argument"),
+    })
+}
+
+// `sum`, `min` and `max` pull items one at a time, so they run in constant
+// memory even over huge ranges.
+pub fn sum(span: &Span, args: Vec<Value>) -> Result<Value> {
+    let (iterable, mut acc) = match args.as_slice() {
+        [iterable] => (iterable, Value::Integer(0)),
+        [iterable, start @ (Value::Integer(_) | Value::Float(_))] => (iterable, start.clone()),
+        [_, start] => error!(span, "sum() start value must be a number, got {}", start.type_name()),
+        _ => error!(span, "sum() expects an iterable and an optional start value"),
+    };
+
+    if let Value::Range(start, end, step) = iterable {
+        let total = match range_sum(*start, *end, *step) {
+            Some(total) => Value::Integer(total),
+            None => error!(span, "Integer overflow in sum()"),
+        };
+        return match (&acc, &total) {
+            (Value::Integer(a), Value::Integer(b)) => match a.checked_add(*b) {
+                Some(res) => Ok(Value::Integer(res)),
+                None => error!(span, "Integer overflow in sum()"),
+            },
+            _ => acc.plus(&total, span),
+        };
+    }
+
+    for item in iterable.iterator(span)? {
+        acc = match (&acc, &item) {
+            (Value::Integer(a), Value::Integer(b)) => match a.checked_add(*b) {
+                Some(res) => Value::Integer(res),
+                None => error!(span, "Integer overflow in sum()"),
+            },
+            (_, Value::Integer(_) | Value::Float(_)) => acc.plus(&item, span)?,
+            _ => error!(span, "sum() can only add numbers, got {}", item.type_name()),
+        }
+    }
+    Ok(acc)
+}
+
+// Closed form for an arithmetic series: `len * (first + last) / 2`
+fn range_sum(start: i64, end: i64, step: i64) -> Option<i64> {
+    let len = range_len(start, end, step) as i128;
+    if len == 0 {
+        return Some(0);
+    }
+    let last = start as i128 + (len - 1) * step as i128;
+    let total = len.checked_mul(start as i128 + last)? / 2;
+    i64::try_from(total).ok()
+}
+
+fn extremum(
+    span: &Span,
+    args: Vec<Value>,
+    name: &str,
+    pick_new: fn(&Value, &Value, &Span) -> Result<Value>,
+) -> Result<Value> {
+    // Either a single iterable, or the candidates passed directly
+    let items = match args.len() {
+        0 => error!(span, "{}() expects at least one argument", name),
+        1 => args[0].iterator(span)?,
+        _ => IteratorValue(make!(args.into_iter())),
+    };
+
+    let mut best: Option<Value> = None;
+    for item in items {
+        best = match best {
+            Some(best) => match pick_new(&item, &best, span)? {
+                Value::Boolean(true) => Some(item),
+                _ => Some(best),
+            },
+            None => Some(item),
+        };
+    }
+    match best {
+        Some(best) => Ok(best),
+        None => error!(span, "{}() of an empty sequence", name),
+    }
+}
+
+pub fn min(span: &Span, args: Vec<Value>) -> Result<Value> {
+    extremum(span, args, "min", Value::less_than)
+}
+
+pub fn max(span: &Span, args: Vec<Value>) -> Result<Value> {
+    extremum(span, args, "max", Value::greater_than)
+}
+
+/// Items `sorted` can order: all numbers or all strings. NaN is rejected,
+/// so the order is total.
+enum SortKey {
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+impl PartialEq for SortKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for SortKey {}
+
+impl PartialOrd for SortKey {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SortKey {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        use SortKey::*;
+        match (self, other) {
+            (Integer(a), Integer(b)) => a.cmp(b),
+            (Integer(a), Float(b)) => (*a as f64).total_cmp(b),
+            (Float(a), Integer(b)) => a.total_cmp(&(*b as f64)),
+            (Float(a), Float(b)) => a.total_cmp(b),
+            (String(a), String(b)) => a.cmp(b),
+            // `sortable` never mixes strings with numbers
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl SortKey {
+    fn into_value(self) -> Value {
+        match self {
+            SortKey::Integer(i) => Value::Integer(i),
+            SortKey::Float(f) => Value::Float(f),
+            SortKey::String(s) => Value::String(make!(s)),
+        }
+    }
+}
+
+fn sortable(items: Vec<Value>, span: &Span) -> Result<Vec<SortKey>> {
+    let mut keys = Vec::with_capacity(items.len());
+    for item in &items {
+        let key = match item {
+            Value::Integer(i) => SortKey::Integer(*i),
+            Value::Float(f) if f.is_nan() => error!(span, "sorted() can't order NaN"),
+            Value::Float(f) => SortKey::Float(*f),
+            Value::String(s) => SortKey::String(s.borrow().clone()),
+            _ => error!(span, "sorted() can't order values of type {}", item.type_name()),
+        };
+        keys.push(key);
+    }
+    let is_string = |key: &SortKey| matches!(key, SortKey::String(_));
+    if let Some(first) = keys.first() {
+        if let Some(i) = keys.iter().position(|key| is_string(key) != is_string(first)) {
+            error!(
+                span,
+                "sorted() can't compare {} with {}",
+                items[0].type_name(),
+                items[i].type_name()
+            )
+        }
+    }
+    Ok(keys)
+}
+
+/// Returns a new array, using the same ordering as `<`. Pass `true` as the
+/// second argument to sort in descending order.
+pub fn sorted(span: &Span, args: Vec<Value>) -> Result<Value> {
+    let (iterable, reverse) = match args.as_slice() {
+        [iterable] => (iterable, false),
+        [iterable, Value::Boolean(reverse)] => (iterable, *reverse),
+        _ => error!(span, "sorted() expects an iterable and an optional boolean"),
+    };
+
+    let items = iterable.iterator(span)?.collect::<Vec<_>>();
+    let mut items = sortable(items, span)?;
+    items.sort_by(|a, b| if reverse { b.cmp(a) } else { a.cmp(b) });
+    let items = items.into_iter().map(SortKey::into_value).collect();
+    Ok(Value::Array(make!(items)))
+}
+
+/// A new array with the items of any iterable in reverse order.
+pub fn reversed(span: &Span, args: Vec<Value>) -> Result<Value> {
+    if args.len() != 1 {
+        error!(span, "reversed() takes exactly one argument");
+    }
+    let mut items = args[0].iterator(span)?.collect::<Vec<_>>();
+    items.reverse();
+    Ok(Value::Array(make!(items)))
+}
+
+/// Shallow copy: a new array holding the same elements. Values that are
+/// never mutated in place are returned as they are.
+pub fn copy(span: &Span, args: Vec<Value>) -> Result<Value> {
+    if args.len() != 1 {
+        error!(span, "copy() takes exactly one argument");
+    }
+    Ok(match &args[0] {
+        Value::Array(array) => Value::Array(make!(array.borrow().clone())),
+        Value::String(string) => Value::String(make!(string.borrow().clone())),
+        Value::Bytes(bytes) => Value::Bytes(make!(bytes.borrow().clone())),
+        other => other.clone(),
+    })
+}
+
+pub fn replace_first(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [Value::String(string), Value::String(old), Value::String(new)] => Ok(Value::String(
+            make!(string.borrow().replacen(get!(old), get!(new), 1)),
+        )),
+        _ => error!(span, "replace_first() expects three strings"),
+    }
+}
+
+pub fn trim_prefix(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [Value::String(string), Value::String(prefix)] => {
+            let string = string.borrow();
+            let res = string.strip_prefix(get!(prefix)).unwrap_or(&string);
+            Ok(Value::String(make!(res.to_string())))
+        }
+        _ => error!(span, "trim_prefix() expects two strings"),
+    }
+}
+
+pub fn trim_suffix(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [Value::String(string), Value::String(suffix)] => {
+            let string = string.borrow();
+            let res = string.strip_suffix(get!(suffix)).unwrap_or(&string);
+            Ok(Value::String(make!(res.to_string())))
+        }
+        _ => error!(span, "trim_suffix() expects two strings"),
+    }
+}
+
+/// Quotes a string so a POSIX shell reads it back as one literal word.
+pub fn shell_quote(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [Value::String(string)] => {
+            let string = string.borrow();
+            let safe = |c: char| c.is_ascii_alphanumeric() || "@%+=:,./-_".contains(c);
+            let res = if !string.is_empty() && string.chars().all(safe) {
+                string.clone()
+            } else {
+                format!("'{}'", string.replace('\'', "'\"'\"'"))
+            };
+            Ok(Value::String(make!(res)))
+        }
+        _ => error!(span, "shell_quote() expects a string"),
+    }
+}
+
+pub fn html_escape(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [Value::String(string)] => {
+            let mut res = String::new();
+            for c in string.borrow().chars() {
+                match c {
+                    '&' => res.push_str("&amp;"),
+                    '<' => res.push_str("&lt;"),
+                    '>' => res.push_str("&gt;"),
+                    '"' => res.push_str("&quot;"),
+                    '\'' => res.push_str("&#39;"),
+                    _ => res.push(c),
+                }
+            }
+            Ok(Value::String(make!(res)))
+        }
+        _ => error!(span, "html_escape() expects a string"),
+    }
+}
+
+/// Percent-encodes every byte except the unreserved characters of RFC 3986,
+/// so the result is safe in any part of a URL.
+pub fn url_encode(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [Value::String(string)] => {
+            let mut res = String::new();
+            for &b in string.borrow().as_bytes() {
+                match b {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                        res.push(b as char)
+                    }
+                    _ => res.push_str(&format!("%{:02X}", b)),
+                }
+            }
+            Ok(Value::String(make!(res)))
+        }
+        _ => error!(span, "url_encode() expects a string"),
+    }
+}
+
+/// Terminates the program with a message and the call stack. Unlike a
+/// runtime error this is never recoverable, so the REPL exits as well.
+pub fn abort(span: &Span, args: Vec<Value>) -> Result<Value> {
+    let message = match args.as_slice() {
+        [] => "abort() called".to_string(),
+        [message] => message.to_string(),
+        _ => error!(span, "abort() takes at most one argument"),
+    };
+    Err(Error {
+        kind: ErrorKind::Abort,
+        span: *span,
+        message,
+        trace: vec![],
+    })
+}
+
+/// Encodes a string as UTF-8. An array of integers in 0..256 is also
+/// accepted, to build arbitrary binary data.
+pub fn bytes(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [Value::String(string)] => Ok(Value::Bytes(make!(string.borrow().as_bytes().to_vec()))),
+        [Value::Array(array)] => {
+            let mut res = vec![];
+            for item in array.borrow().iter() {
+                match item {
+                    Value::Integer(b @ 0..=255) => res.push(*b as u8),
+                    _ => error!(span, "bytes() expects integers between 0 and 255, got {:?}", item),
+                }
+            }
+            Ok(Value::Bytes(make!(res)))
+        }
+        _ => error!(span, "bytes() expects a string or an array of integers"),
+    }
+}
+
+pub fn decode(span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [Value::Bytes(bytes)] => match String::from_utf8(bytes.borrow().clone()) {
+            Ok(string) => Ok(Value::String(make!(string))),
+            Err(err) => error!(span, "decode() got invalid UTF-8: {}", err),
+        },
+        _ => error!(span, "decode() expects bytes"),
+    }
+}
+
+pub fn map(interpreter: &mut Interpreter, span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [func, iterable] => {
+            let mut res = vec![];
+            for item in iterable.iterator(span)? {
+                res.push(interpreter.call(func, vec![item], span)?);
+            }
+            Ok(Value::Array(make!(res)))
+        }
+        _ => error!(span, "map() expects a function and an iterable"),
+    }
+}
+
+/// Keeps the items for which the predicate returns a truthy value.
+pub fn filter(interpreter: &mut Interpreter, span: &Span, args: Vec<Value>) -> Result<Value> {
+    match args.as_slice() {
+        [func, iterable] => {
+            let mut res = vec![];
+            for item in iterable.iterator(span)? {
+                if interpreter.call(func, vec![item.clone()], span)?.is_truthy() {
+                    res.push(item);
+                }
+            }
+            Ok(Value::Array(make!(res)))
+        }
+        _ => error!(span, "filter() expects a function and an iterable"),
+    }
+}
+
+/// Folds a two-argument function over an iterable, starting from the
+/// initial value if given, or else the first item.
+pub fn reduce(interpreter: &mut Interpreter, span: &Span, args: Vec<Value>) -> Result<Value> {
+    let (func, iterable, initial) = match args.as_slice() {
+        [func, iterable] => (func, iterable, None),
+        [func, iterable, initial] => (func, iterable, Some(initial.clone())),
+        _ => error!(span, "reduce() expects a function, an iterable and an optional initial value"),
+    };
+
+    let mut items = iterable.iterator(span)?;
+    let mut acc = match initial.or_else(|| items.next()) {
+        Some(acc) => acc,
+        None => error!(span, "reduce() of an empty iterable with no initial value"),
+    };
+    for item in items {
+        acc = interpreter.call(func, vec![acc, item], span)?;
+    }
+    Ok(acc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::AST;
+
+    /// Writes `source` to a temporary file, since `dbg` reads its label
+    /// back from the file, and returns the span of its first statement.
+    fn first_statement_span(name: &str, source: &str) -> Span {
+        let path = std::env::temp_dir().join(format!("serpens-{}-{}.sp", name, std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        let filename = path.to_string_lossy().into_owned();
+        let filename: &'static str = Box::leak(filename.into_boxed_str());
+        let tokens = crate::lexer::Lexer::new(source.to_string(), filename).lex().unwrap();
+        let ast = crate::parser::Parser::new(tokens).parse().unwrap();
+        match ast.as_ref() {
+            AST::Block(_, statements) => *statements[0].span(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn span() -> Span {
+        let loc = crate::common::Location {
+            line: 1,
+            column: 1,
+            char_column: 1,
+            filename: "<test>",
+        };
+        Span(loc, loc)
+    }
+
+    fn array(items: Vec<Value>) -> Value {
+        Value::Array(make!(items))
+    }
+
+    fn string(s: &str) -> Value {
+        Value::String(make!(s.to_string()))
+    }
+
+    #[test]
+    fn sorted_integers() {
+        let items = array(vec![Value::Integer(3), Value::Integer(1), Value::Integer(2)]);
+        let expected = array(vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)]);
+        assert!(sorted(&span(), vec![items.clone()]).unwrap() == expected);
+        let descending = sorted(&span(), vec![items, Value::Boolean(true)]).unwrap();
+        assert_eq!(descending.repr(), "[3, 2, 1]");
+    }
+
+    #[test]
+    fn sorted_mixed_numbers() {
+        let items = array(vec![
+            Value::Float(2.5),
+            Value::Integer(3),
+            Value::Float(-1.0),
+            Value::Integer(1),
+        ]);
+        assert_eq!(sorted(&span(), vec![items]).unwrap().repr(), "[-1, 1, 2.5, 3]");
+    }
+
+    #[test]
+    fn sorted_strings() {
+        let items = array(vec![string("pear"), string("apple"), string("fig")]);
+        assert_eq!(sorted(&span(), vec![items]).unwrap().repr(), r#"["apple", "fig", "pear"]"#);
+        assert_eq!(sorted(&span(), vec![string("cab")]).unwrap().repr(), r#"["a", "b", "c"]"#);
+    }
+
+    #[test]
+    fn sorted_rejects_incomparable_items() {
+        let mixed = array(vec![Value::Integer(1), string("a")]);
+        assert!(sorted(&span(), vec![mixed]).is_err());
+        let nan = array(vec![Value::Float(3.0), Value::Float(f64::NAN), Value::Float(1.0)]);
+        assert!(sorted(&span(), vec![nan]).is_err());
+        let nested = array(vec![array(vec![]), array(vec![])]);
+        assert!(sorted(&span(), vec![nested]).is_err());
+    }
+
+    #[test]
+    fn dbg_label_with_space_indent() {
+        let span = first_statement_span("dbg-spaces", "    dbg(3 * 4)\n");
+        assert_eq!(dbg_label(&span).as_deref(), Some("3 * 4"));
+    }
+
+    #[test]
+    fn dbg_label_with_tab_indent() {
+        let span = first_statement_span("dbg-tabs", "\tdbg(3 * 4)\n");
+        assert_eq!(span.0.column, 9);
+        assert_eq!(dbg_label(&span).as_deref(), Some("3 * 4"));
+    }
+
+    #[test]
+    fn joined_uses_the_separator() {
+        let args = [Value::Integer(1), string("a"), Value::Nothing];
+        assert_eq!(joined(&args, " "), "1 \"a\" nothing");
+        assert_eq!(joined(&args, ", "), "1, \"a\", nothing");
+        assert_eq!(joined(&args, ""), "1\"a\"nothing");
+        assert_eq!(joined(&[], "-"), "");
+    }
+}
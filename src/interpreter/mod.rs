@@ -1,11 +1,13 @@
-use crate::ast::AST;
+use crate::ast::{Comparison, Operator, AST};
 use crate::common::{make, Ref, Span};
-use crate::error::{runtime_error as error, Result};
-use crate::interpreter::value::{IteratorValue, Value, Function};
+use crate::error::{runtime_error as error, Frame, Result};
+use crate::interpreter::value::{Value, Function};
 use std::collections::HashMap;
 use std::rc::Rc;
 
 mod builtin;
+#[cfg(test)]
+mod tests;
 pub mod value;
 
 #[derive(Debug)]
@@ -36,6 +38,16 @@ impl Scope {
         Ok(())
     }
 
+    fn remove(&mut self, name: &str) -> Option<Value> {
+        match self.vars.remove(name) {
+            Some(value) => Some(value),
+            None => match &self.parent {
+                Some(parent) => parent.borrow_mut().remove(name),
+                None => None,
+            },
+        }
+    }
+
     fn get(&self, name: &str) -> Option<Value> {
         if self.vars.contains_key(name) {
             self.vars.get(name).cloned()
@@ -57,32 +69,160 @@ enum ControlFlow {
 }
 
 type BuiltInFunctionType = fn(&Span, Vec<Value>) -> Result<Value>;
+type InterpreterFunctionType = fn(&mut Interpreter, &Span, Vec<Value>) -> Result<Value>;
+
+/// Most builtins are plain functions of their arguments. The ones that call
+/// back into user code (e.g. `map`) also get the interpreter.
+#[derive(Clone, Copy)]
+enum BuiltIn {
+    Plain(BuiltInFunctionType),
+    WithInterpreter(InterpreterFunctionType),
+}
+
+// Native stack reserved per nested call. Each call goes through several
+// `run` frames; measured at roughly 200-700 KiB per call in debug builds and
+// 5-16 KiB in release, depending on how deeply the call is nested in the
+// function body, so these budgets leave some headroom.
+const STACK_PER_CALL: usize = if cfg!(debug_assertions) { 1024 * 1024 } else { 32 * 1024 };
+
+// The most stack the interpreter thread ever reserves.
+const MAX_STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// The highest recursion limit that fits in `MAX_STACK_SIZE`: 8191 nested
+/// calls in release builds, but only 255 in debug builds.
+pub const MAX_CALL_DEPTH: usize = MAX_STACK_SIZE / STACK_PER_CALL - 1;
+
+pub const DEFAULT_MAX_CALL_DEPTH: usize =
+    if MAX_CALL_DEPTH < 1000 { MAX_CALL_DEPTH } else { 1000 };
+
+/// Native stack needed to run `max_call_depth` nested calls, which is at
+/// most `MAX_STACK_SIZE` for depths up to `MAX_CALL_DEPTH`.
+pub fn stack_size(max_call_depth: usize) -> usize {
+    max_call_depth.saturating_add(1).saturating_mul(STACK_PER_CALL).min(MAX_STACK_SIZE)
+}
 
 pub struct Interpreter {
-    builtins: HashMap<&'static str, BuiltInFunctionType>,
+    builtins: HashMap<&'static str, BuiltIn>,
     control_flow: ControlFlow,
+    /// Loops entered in the function currently running, so `break` and
+    /// `continue` can't escape into a caller's loop.
+    loop_depth: usize,
+    call_depth: usize,
+    max_call_depth: usize,
 }
 
+/// Methods that just call the builtin of the same name with the receiver
+/// as the first argument, e.g. `s.split(",")` is `split(s, ",")`.
+const METHODS: &[(&str, &str)] = &[
+    ("string", "upper"),
+    ("string", "lower"),
+    ("string", "strip"),
+    ("string", "split"),
+    ("string", "join"),
+    ("string", "len"),
+    ("string", "capitalize"),
+    ("string", "title"),
+    ("list", "len"),
+    ("bytes", "len"),
+    ("bytes", "decode"),
+];
+
 macro_rules! builtins {
-    ($($name:ident),+ $(,)?) => {
-        HashMap::from([$(
-            (
-                stringify!($name),
-                builtin::$name as BuiltInFunctionType,
-            ),
-        )+])
+    (
+        $($name:ident),+ $(,)?;
+        with_interpreter: $($interp_name:ident),+ $(,)?
+    ) => {
+        HashMap::from([
+            $((
+                // Builtins named after keywords are raw identifiers, e.g. `r#type`
+                stringify!($name).trim_start_matches("r#"),
+                BuiltIn::Plain(builtin::$name),
+            ),)+
+            $((
+                stringify!($interp_name),
+                BuiltIn::WithInterpreter(builtin::$interp_name),
+            ),)+
+        ])
     };
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        let builtins = builtins!(print, len, exit);
+        let builtins = builtins!(
+            print,
+            print_sep,
+            write,
+            format,
+            printf,
+            len,
+            str,
+            int,
+            float,
+            r#type,
+            range,
+            exit,
+            list,
+            take,
+            skip,
+            chain,
+            enumerate,
+            group_digits,
+            approx_eq,
+            upper,
+            lower,
+            strip,
+            split,
+            join,
+            ord,
+            chr,
+            capitalize,
+            title,
+            dbg,
+            product,
+            sum,
+            abs,
+            sqrt,
+            floor,
+            ceil,
+            round,
+            pow,
+            min,
+            max,
+            sorted,
+            reversed,
+            copy,
+            replace_first,
+            trim_prefix,
+            trim_suffix,
+            shell_quote,
+            html_escape,
+            url_encode,
+            abort,
+            bytes,
+            decode;
+
+            with_interpreter:
+            map,
+            filter,
+            reduce,
+        );
         Self {
             builtins,
             control_flow: ControlFlow::None,
+            loop_depth: 0,
+            call_depth: 0,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
         }
     }
 
+    /// How many nested user function calls are allowed before raising an
+    /// error, which keeps runaway recursion from overflowing the native
+    /// stack. The thread running the interpreter needs `stack_size` of it,
+    /// so depths past `MAX_CALL_DEPTH` are clamped.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth.min(MAX_CALL_DEPTH);
+    }
+
     pub fn execute(&mut self, ast: &Rc<AST>) -> Result<Value> {
         let scope = Scope::new(None, false);
         self.run(ast, scope)
@@ -98,6 +238,9 @@ impl Interpreter {
                 let mut last = None;
                 for stmt in stmts {
                     last = Some(self.run(stmt, scope.clone())?);
+                    if !matches!(self.control_flow, ControlFlow::None) {
+                        break;
+                    }
                 }
                 Ok(last.unwrap_or_else(|| Value::Nothing))
             }
@@ -124,19 +267,66 @@ impl Interpreter {
             AST::IntegerLiteral(_, num) => Value::Integer(*num),
             AST::FloatLiteral(_, num) => Value::Float(*num),
             AST::StringLiteral(_, string) => Value::String(make!(string.clone())),
+            AST::FormatString(_, parts) => {
+                let mut res = String::new();
+                for part in parts {
+                    res.push_str(&self.run(part, scope.clone())?.to_string());
+                }
+                Value::String(make!(res))
+            }
             AST::Nothing(_) => Value::Nothing,
 
             AST::Plus(span, left, right) => dispatch_op!(span, Value::plus, left, right),
             AST::Minus(span, left, right) => dispatch_op!(span, Value::minus, left, right),
             AST::Multiply(loc, left, right) => dispatch_op!(loc, Value::multiply, left, right),
             AST::Divide(loc, left, right) => dispatch_op!(loc, Value::divide, left, right),
-
+            AST::FloorDivide(loc, left, right) => dispatch_op!(loc, Value::floor_divide, left, right),
+            AST::Modulo(loc, left, right) => dispatch_op!(loc, Value::modulo, left, right),
+            AST::Power(loc, left, right) => dispatch_op!(loc, Value::power, left, right),
+
+            AST::Negate(loc, expr) => dispatch_op!(loc, Value::negate, expr),
+            AST::BitNot(loc, expr) => dispatch_op!(loc, Value::bit_not, expr),
+            AST::BitAnd(loc, left, right) => dispatch_op!(loc, Value::bit_and, left, right),
+            AST::BitOr(loc, left, right) => dispatch_op!(loc, Value::bit_or, left, right),
+            AST::BitXor(loc, left, right) => dispatch_op!(loc, Value::bit_xor, left, right),
+            AST::ShiftLeft(loc, left, right) => dispatch_op!(loc, Value::shift_left, left, right),
+            AST::ShiftRight(loc, left, right) => dispatch_op!(loc, Value::shift_right, left, right),
             AST::Not(loc, expr) => dispatch_op!(loc, Value::not, expr),
-            AST::And(loc, left, right) => dispatch_op!(loc, Value::and, left, right),
-            AST::Or(loc, left, right) => dispatch_op!(loc, Value::or, left, right),
+
+            // `and`/`or` only evaluate the right side if the left doesn't
+            // already decide the result
+            AST::And(loc, left, right) => match self.run(left, scope.clone())? {
+                Value::Boolean(false) => Value::Boolean(false),
+                Value::Boolean(true) => match self.run(right, scope)? {
+                    Value::Boolean(right) => Value::Boolean(right),
+                    _ => error!(loc, "Invalid types for and"),
+                },
+                _ => error!(loc, "Invalid types for and"),
+            },
+            AST::Or(loc, left, right) => match self.run(left, scope.clone())? {
+                Value::Boolean(true) => Value::Boolean(true),
+                Value::Boolean(false) => match self.run(right, scope)? {
+                    Value::Boolean(right) => Value::Boolean(right),
+                    _ => error!(loc, "Invalid types for or"),
+                },
+                _ => error!(loc, "Invalid types for or"),
+            },
 
             AST::Equals(loc, left, right) => dispatch_op!(loc, Value::equals, left, right),
             AST::NotEquals(loc, left, right) => dispatch_op!(loc, Value::not_equals, left, right),
+            AST::ChainedComparison(span, first, rest) => {
+                let mut left = self.run(first, scope.clone())?;
+                for (op, right) in rest {
+                    let right = self.run(right, scope.clone())?;
+                    if !compare(*op, &left, &right, span)?.is_truthy() {
+                        return Ok(Value::Boolean(false));
+                    }
+                    left = right;
+                }
+                Value::Boolean(true)
+            }
+            AST::In(loc, left, right) => dispatch_op!(loc, Value::is_in, left, right),
+            AST::NotIn(loc, left, right) => dispatch_op!(loc, Value::not_in, left, right),
             AST::LessThan(loc, left, right) => dispatch_op!(loc, Value::less_than, left, right),
 
             AST::GreaterThan(loc, left, right) => {
@@ -149,27 +339,29 @@ impl Interpreter {
                 dispatch_op!(loc, Value::greater_equals, left, right)
             }
 
-            AST::Call(span, func, args) => self.handle_call(scope, span, func, args)?,
+            AST::Call(span, func, args, kwargs) => {
+                self.handle_call(scope, span, func, args, kwargs)?
+            }
 
             AST::Function {
                 name,
                 args,
+                variadic,
                 body,
                 span,
-                ..
             } => {
                 let func = Value::Function(make!(Function {
                     span: *span,
                     name: name.clone().unwrap_or_else(|| "<anon>".to_string()),
                     args: args.clone(),
+                    variadic: variadic.clone(),
                     body: body.clone(),
                     scope: scope.clone(),
                 }));
-                match name {
-                    Some(name) => scope
+                if let Some(name) = name {
+                    scope
                         .borrow_mut()
-                        .insert(name, func.clone(), false, span)?,
-                    None => {}
+                        .insert(name, func.clone(), false, span)?;
                 }
                 func
             }
@@ -203,7 +395,7 @@ impl Interpreter {
             }
 
             AST::Variable(span, name) => {
-                if self.builtins.get(name.as_str()).is_some() {
+                if self.builtins.contains_key(name.as_str()) {
                     Value::BuiltInFunction(make!(name.clone()))
                 } else if let Some(value) = scope.borrow_mut().get(name) {
                     value
@@ -226,6 +418,27 @@ impl Interpreter {
                 value
             }
 
+            AST::CompoundAssignment(span, op, lhs, value) => match &**lhs {
+                // The container and index are evaluated only once, so any
+                // side effects in them happen once as well.
+                AST::Index(_, left, index) => {
+                    let left = self.run(left, scope.clone())?;
+                    let index = self.run(index, scope.clone())?;
+                    let current = left.index(&index, span)?;
+                    let value = self.run(value, scope)?;
+                    let value = apply_operator(*op, &current, &value, span)?;
+                    left.set_index(&index, value.clone(), span)?;
+                    value
+                }
+                _ => {
+                    let current = self.run(lhs, scope.clone())?;
+                    let value = self.run(value, scope.clone())?;
+                    let value = apply_operator(*op, &current, &value, span)?;
+                    self.handle_assign(scope, span, lhs, value.clone())?;
+                    value
+                }
+            },
+
             AST::VarDeclaration(span, name, value) => {
                 if self.builtins.contains_key(name.as_str()) {
                     error!(
@@ -240,86 +453,171 @@ impl Interpreter {
                 value
             }
 
-            AST::Assert(loc, cond) => {
+            AST::MultiDeclaration(span, names, values) => {
+                for name in names {
+                    if self.builtins.contains_key(name.as_str()) {
+                        error!(
+                            span,
+                            "`{}` is a built-in function, can't be used as a variable", name
+                        )
+                    }
+                }
+                let values = self.run_multiple(span, names.len(), values, scope.clone())?;
+                for (name, value) in names.iter().zip(values) {
+                    scope.borrow_mut().insert(name, value, false, span)?;
+                }
+                Value::Nothing
+            }
+
+            AST::MultiAssignment(span, targets, values) => {
+                let values = self.run_multiple(span, targets.len(), values, scope.clone())?;
+                for (target, value) in targets.iter().zip(values) {
+                    self.handle_assign(scope.clone(), span, target, value)?;
+                }
+                Value::Nothing
+            }
+
+            AST::Del(span, target) => {
+                self.handle_del(scope, span, target)?;
+                Value::Nothing
+            }
+
+            AST::Assert(loc, cond, message) => {
                 let cond = self.run(cond, scope)?;
                 match cond {
                     Value::Boolean(true) => {}
-                    Value::Boolean(false) => error!(loc, "Assertion failed"),
+                    Value::Boolean(false) => match message {
+                        Some(message) => error!(loc, "Assertion failed: {}", message),
+                        None => error!(loc, "Assertion failed"),
+                    },
                     _ => error!(loc, "Assertion condition must be a boolean"),
                 }
                 Value::Nothing
             }
 
-            AST::If(span, cond, body, else_body) => {
-                let cond = self.run(cond, scope.clone())?;
-                match cond {
-                    Value::Boolean(true) => self.run(body, scope)?,
-                    Value::Boolean(false) => match else_body {
+            AST::If(_, cond, body, else_body) => {
+                if self.run(cond, scope.clone())?.is_truthy() {
+                    self.run(body, scope)?
+                } else {
+                    match else_body {
                         Some(else_body) => self.run(else_body, scope)?,
                         None => Value::Nothing,
-                    },
-                    _ => error!(span, "If condition must be a boolean"),
+                    }
                 }
             }
 
-            AST::While(span, cond, body) => {
-                loop {
-                    let cond = self.run(cond, scope.clone())?;
-                    match cond {
-                        Value::Boolean(true) => {
-                            self.run(body, scope.clone())?;
-                            match self.control_flow {
-                                ControlFlow::None => {}
-                                ControlFlow::Continue => self.control_flow = ControlFlow::None,
-                                ControlFlow::Break => {
-                                    self.control_flow = ControlFlow::None;
-                                    break;
-                                }
-                                ControlFlow::Return(_) => break,
-                            }
+            AST::Match(_, value, arms, default) => {
+                let value = self.run(value, scope.clone())?;
+                for (pattern, body) in arms {
+                    if self.run(pattern, scope.clone())? == value {
+                        return self.run(body, scope);
+                    }
+                }
+                match default {
+                    Some(default) => self.run(default, scope)?,
+                    None => Value::Nothing,
+                }
+            }
+
+            AST::While(..) | AST::ForEach(..) | AST::For { .. } => {
+                self.loop_depth += 1;
+                let res = self.run_loop(ast, scope);
+                self.loop_depth -= 1;
+                res?
+            }
+
+            AST::Range(span, start, end) => {
+                let start = self.run(start, scope.clone())?;
+                let end = self.run(end, scope)?;
+                Value::create_range(&start, &end, span)?
+            }
+
+            AST::Break(span) => {
+                if self.loop_depth == 0 {
+                    error!(span, "`break` outside of loop")
+                }
+                self.control_flow = ControlFlow::Break;
+                Value::Nothing
+            }
+            AST::Continue(span) => {
+                if self.loop_depth == 0 {
+                    error!(span, "`continue` outside of loop")
+                }
+                self.control_flow = ControlFlow::Continue;
+                Value::Nothing
+            }
+
+            AST::Member(span, left, name) => self.run(left, scope)?.member(name, span)?,
+
+            AST::Index(span, left, right) => {
+                let left = self.run(left, scope.clone())?;
+                let right = self.run(right, scope)?;
+                left.index(&right, span)?
+            },
+
+            AST::PostIncrement(span, expr, offset) => {
+                self.handle_increment(scope, span, expr, *offset)?.0
+            },
+            AST::PreIncrement(span, expr, offset) => {
+                self.handle_increment(scope, span, expr, *offset)?.1
+            },
+
+            AST::ArrayLiteral(_, arr) => {
+                Value::Array(make!(
+                    arr.iter()
+                        .map(|x| self.run(x, scope.clone()))
+                        .collect::<Result<Vec<_>>>()?
+                ))
+            }
+        })
+    }
+
+    fn run_loop(&mut self, ast: &Rc<AST>, scope: Ref<Scope>) -> Result<Value> {
+        Ok(match ast.as_ref() {
+            AST::While(_, cond, body) => {
+                while self.run(cond, scope.clone())?.is_truthy() {
+                    self.run(body, scope.clone())?;
+                    match self.control_flow {
+                        ControlFlow::None => {}
+                        ControlFlow::Continue => self.control_flow = ControlFlow::None,
+                        ControlFlow::Break => {
+                            self.control_flow = ControlFlow::None;
+                            break;
                         }
-                        Value::Boolean(false) => break,
-                        _ => error!(span, "While condition must be a boolean"),
-                    };
+                        ControlFlow::Return(_) => break,
+                    }
                 }
                 Value::Nothing
             }
 
             AST::ForEach(span, loop_var, iter, body) => {
                 let val = self.run(iter, scope.clone())?;
-                let iter = val.iterator(span)?;
-                match iter {
-                    Value::Iterator(IteratorValue(iter)) => {
-                        let iter = &mut *(*iter).borrow_mut();
-                        for val in iter {
-                            let loop_scope =
-                                Scope::new(Some(scope.clone()), scope.borrow_mut().in_function);
-                            loop_scope
-                                .borrow_mut()
-                                .insert(loop_var, val.clone(), false, span)?;
-                            self.run(body, loop_scope)?;
-                            match self.control_flow {
-                                ControlFlow::None => {}
-                                ControlFlow::Continue => self.control_flow = ControlFlow::None,
-                                ControlFlow::Break => {
-                                    self.control_flow = ControlFlow::None;
-                                    break;
-                                }
-                                ControlFlow::Return(_) => break,
-                            }
+                for val in val.iterator(span)? {
+                    let loop_scope =
+                        Scope::new(Some(scope.clone()), scope.borrow_mut().in_function);
+                    loop_scope
+                        .borrow_mut()
+                        .insert(loop_var, val.clone(), false, span)?;
+                    self.run(body, loop_scope)?;
+                    match self.control_flow {
+                        ControlFlow::None => {}
+                        ControlFlow::Continue => self.control_flow = ControlFlow::None,
+                        ControlFlow::Break => {
+                            self.control_flow = ControlFlow::None;
+                            break;
                         }
+                        ControlFlow::Return(_) => break,
                     }
-                    _ => error!(span, "For loop must iterate over an iterable"),
-                };
+                }
                 Value::Nothing
             }
 
             AST::For {
-                span,
                 init,
                 cond,
                 step,
                 body,
+                ..
             } => {
                 let loop_scope = Scope::new(Some(scope.clone()), scope.borrow().in_function);
                 if let Some(init) = init {
@@ -327,12 +625,9 @@ impl Interpreter {
                 }
                 loop {
                     if let Some(cond) = cond {
-                        let cond = self.run(cond, loop_scope.clone())?;
-                        match cond {
-                            Value::Boolean(true) => {}
-                            Value::Boolean(false) => break,
-                            _ => error!(span, "For condition must be a boolean"),
-                        };
+                        if !self.run(cond, loop_scope.clone())?.is_truthy() {
+                            break;
+                        }
                     }
                     self.run(body, loop_scope.clone())?;
                     match self.control_flow {
@@ -350,61 +645,32 @@ impl Interpreter {
                 }
                 Value::Nothing
             }
-
-            AST::Range(span, start, end) => {
-                let start = self.run(start, scope.clone())?;
-                let end = self.run(end, scope)?;
-                Value::create_range(&start, &end, span)?
-            }
-
-            AST::Break(_) => {
-                self.control_flow = ControlFlow::Break;
-                Value::Nothing
-            }
-            AST::Continue(_) => {
-                self.control_flow = ControlFlow::Continue;
-                Value::Nothing
-            }
-
-            AST::Index(span, left, right) => {
-                let left = self.run(left, scope.clone())?;
-                let right = self.run(right, scope)?;
-                left.index(&right, span)?
-            },
-
-            AST::PostIncrement(span, expr, offset) => {
-                let value = self.run(expr, scope.clone())?;
-                match &value {
-                    Value::Integer(val) => {
-                        let new_val = Value::Integer(*val + offset);
-                        self.handle_assign(scope, span, expr, new_val)?;
-                    }
-                    _ => error!(span, "Operation only supported for integers"),
-                }
-                value
-            },
-            AST::PreIncrement(span, expr, offset) => {
-                let value = self.run(expr, scope.clone())?;
-                match &value {
-                    Value::Integer(val) => {
-                        let new_val = Value::Integer(*val + offset);
-                        self.handle_assign(scope, span, expr, new_val.clone())?;
-                        new_val
-                    }
-                    _ => error!(span, "Operation only supported for integers"),
-                }
-            },
-
-            AST::ArrayLiteral(_, arr) => {
-                Value::Array(make!(
-                    arr.iter()
-                        .map(|x| self.run(x, scope.clone()))
-                        .collect::<Result<Vec<_>>>()?
-                ))
-            }
+            _ => unreachable!("run_loop called on non-loop"),
         })
     }
 
+    /// Evaluates every right-hand side before anything is bound, so that
+    /// `a, b = b, a` swaps. A single value is unpacked instead.
+    fn run_multiple(
+        &mut self,
+        span: &Span,
+        count: usize,
+        values: &[Rc<AST>],
+        scope: Ref<Scope>,
+    ) -> Result<Vec<Value>> {
+        let values = match values {
+            [value] => self.run(value, scope)?.iterator(span)?.collect::<Vec<_>>(),
+            _ => values
+                .iter()
+                .map(|value| self.run(value, scope.clone()))
+                .collect::<Result<Vec<_>>>()?,
+        };
+        if values.len() != count {
+            error!(span, "Expected {} values to unpack, got {}", count, values.len())
+        }
+        Ok(values)
+    }
+
     fn handle_assign(
         &mut self,
         scope: Ref<Scope>,
@@ -424,53 +690,291 @@ impl Interpreter {
                     .borrow_mut()
                     .insert(name.as_str(), value, true, span)?;
             }
+            AST::Index(span, left, index) => {
+                let left = self.run(left, scope.clone())?;
+                let index = self.run(index, scope)?;
+                left.set_index(&index, value, span)?;
+            }
             _ => error!(span, "Invalid assignment target"),
         }
         Ok(())
     }
 
+    /// Adds `offset` to an integer variable or element in place, returning
+    /// the old and new values. Like compound assignment, an index target's
+    /// container and index are evaluated only once.
+    fn handle_increment(
+        &mut self,
+        scope: Ref<Scope>,
+        span: &Span,
+        target: &Rc<AST>,
+        offset: i64,
+    ) -> Result<(Value, Value)> {
+        let increment = |value: &Value| match value {
+            Value::Integer(val) => match val.checked_add(offset) {
+                Some(val) => Ok(Value::Integer(val)),
+                None => error!(span, "Integer overflow in increment"),
+            },
+            _ => error!(span, "Operation only supported for integers"),
+        };
+        match &**target {
+            AST::Variable(..) => {
+                let value = self.run(target, scope.clone())?;
+                let new_value = increment(&value)?;
+                self.handle_assign(scope, span, target, new_value.clone())?;
+                Ok((value, new_value))
+            }
+            AST::Index(_, left, index) => {
+                let left = self.run(left, scope.clone())?;
+                let index = self.run(index, scope)?;
+                let value = left.index(&index, span)?;
+                let new_value = increment(&value)?;
+                left.set_index(&index, new_value.clone(), span)?;
+                Ok((value, new_value))
+            }
+            _ => error!(span, "Invalid increment target"),
+        }
+    }
+
+    fn handle_del(&mut self, scope: Ref<Scope>, span: &Span, target: &Rc<AST>) -> Result<()> {
+        match &**target {
+            AST::Variable(span, name) => {
+                if self.builtins.contains_key(name.as_str()) {
+                    error!(span, "`{}` is a built-in function, can't delete it", name)
+                }
+                if scope.borrow_mut().remove(name).is_none() {
+                    error!(span, "Variable {} not found", name)
+                }
+            }
+            AST::Index(span, left, index) => {
+                let left = self.run(left, scope.clone())?;
+                let index = self.run(index, scope)?;
+                match (&left, &index) {
+                    (Value::Array(arr), Value::Integer(i)) => {
+                        let mut arr = arr.borrow_mut();
+                        if *i < 0 || *i as usize >= arr.len() {
+                            error!(span, "Index out of bounds")
+                        }
+                        arr.remove(*i as usize);
+                    }
+                    _ => error!(span, "Can't delete index {:?} of {:?}", index, left),
+                }
+            }
+            _ => error!(span, "Invalid del target"),
+        }
+        Ok(())
+    }
+
     fn handle_call(
         &mut self,
         scope: Ref<Scope>,
         span: &Span,
         func: &Rc<AST>,
         args: &[Rc<AST>],
+        kwargs: &[(String, Rc<AST>)],
     ) -> Result<Value> {
+        // Arguments are evaluated in source order: the callee, then the
+        // positional arguments, then the keyword arguments.
+        if let AST::Member(_, object, name) = &**func {
+            if !kwargs.is_empty() {
+                error!(span, "Methods don't take keyword arguments")
+            }
+            let object = self.run(object, scope.clone())?;
+            let args = args
+                .iter()
+                .map(|arg| self.run(arg, scope.clone()))
+                .collect::<Result<Vec<_>>>()?;
+            return self.call_method(object, name, args, span);
+        }
+
         let func = self.run(func, scope.clone())?;
         let args = args
             .iter()
             .map(|arg| self.run(arg, scope.clone()))
             .collect::<Result<Vec<_>>>()?;
+        if kwargs.is_empty() {
+            return self.call(&func, args, span);
+        }
+        let kwargs = kwargs
+            .iter()
+            .map(|(name, arg)| Ok((name.as_str(), self.run(arg, scope.clone())?)))
+            .collect::<Result<Vec<_>>>()?;
+        let args = match &func {
+            Value::Function(func) => bind_keywords(&func.borrow().args, args, kwargs, span)?,
+            _ => error!(span, "Only user-defined functions take keyword arguments"),
+        };
+        self.call(&func, args, span)
+    }
 
-        return Ok(match func {
+    fn call_method(
+        &mut self,
+        object: Value,
+        name: &str,
+        mut args: Vec<Value>,
+        span: &Span,
+    ) -> Result<Value> {
+        let type_name = object.type_name();
+        match (&object, name) {
+            // Sorts in place, unlike the `sorted` builtin
+            (Value::Array(array), "sort") => {
+                args.insert(0, object.clone());
+                if let Value::Array(sorted) = builtin::sorted(span, args)? {
+                    array.swap(&sorted);
+                }
+                Ok(Value::Nothing)
+            }
+            // Reverses in place, unlike the `reversed` builtin
+            (Value::Array(array), "reverse") => {
+                if !args.is_empty() {
+                    error!(span, "reverse() takes no arguments")
+                }
+                array.borrow_mut().reverse();
+                Ok(Value::Nothing)
+            }
+            _ if METHODS.contains(&(type_name, name)) => {
+                args.insert(0, object);
+                self.call(&Value::BuiltInFunction(make!(name.to_string())), args, span)
+            }
+            _ => error!(span, "No method `{}` on type {}", name, type_name),
+        }
+    }
+
+    /// Calls a user or built-in function with already evaluated arguments.
+    pub fn call(&mut self, func: &Value, args: Vec<Value>, span: &Span) -> Result<Value> {
+        Ok(match func {
             Value::Function(func) => {
                 let new_scope = Scope::new(Some(func.borrow().scope.clone()), true);
-                if args.len() != func.borrow().args.len() {
-                    error!(
-                        *span,
-                        "Expected {} arguments, got {}",
-                        func.borrow().args.len(),
-                        args.len()
-                    )
+                let mut args = args;
+                let mut rest = None;
+                {
+                    let func = func.borrow();
+                    match &func.variadic {
+                        Some(variadic) if args.len() >= func.args.len() => {
+                            let value = Value::Array(make!(args.split_off(func.args.len())));
+                            rest = Some((variadic.clone(), value.clone()));
+                            new_scope.borrow_mut().insert(variadic, value, false, span)?;
+                        }
+                        Some(_) => error!(
+                            *span,
+                            "Expected at least {} arguments, got {}",
+                            func.args.len(),
+                            args.len()
+                        ),
+                        None if args.len() != func.args.len() => error!(
+                            *span,
+                            "Expected {} arguments, got {}",
+                            func.args.len(),
+                            args.len()
+                        ),
+                        None => {}
+                    }
                 }
+                // The values as called with, for the trace if the body fails,
+                // since the body may reassign its parameters
+                let mut bound = vec![];
                 for (arg, value) in func.borrow().args.iter().zip(args) {
+                    bound.push((arg.clone(), value.clone()));
                     new_scope.borrow_mut().insert(arg, value, false, span)?;
                 }
+                bound.extend(rest);
+                if self.call_depth >= self.max_call_depth {
+                    error!(span, "Maximum recursion depth exceeded")
+                }
                 let body = func.borrow().body.clone();
-                self.run(&body, new_scope)?;
+                let loop_depth = std::mem::take(&mut self.loop_depth);
+                self.call_depth += 1;
+                let res = self.run(&body, new_scope.clone());
+                self.call_depth -= 1;
+                self.loop_depth = loop_depth;
+                let last = res.map_err(|mut err| {
+                    let args = bound
+                        .into_iter()
+                        .map(|(name, value)| (name, truncate_repr(value.repr())))
+                        .collect();
+                    err.trace.push(Frame {
+                        name: func.borrow().name.clone(),
+                        span: *span,
+                        args,
+                    });
+                    err
+                })?;
+                // Without an explicit `return`, the body's last value is returned
                 let value = if let ControlFlow::Return(value) = &self.control_flow {
                     value.clone()
                 } else {
-                    Value::Nothing
+                    last
                 };
                 self.control_flow = ControlFlow::None;
                 value
             }
             Value::BuiltInFunction(func) => match self.builtins.get(func.borrow().as_str()) {
-                Some(func) => func(span, args)?,
+                Some(BuiltIn::Plain(func)) => func(span, args)?,
+                Some(BuiltIn::WithInterpreter(func)) => func(self, span, args)?,
                 None => error!(span, "Built-in function {} not found", func.borrow()),
             },
             x => error!(span, "Can't call object {:?}", x),
-        });
+        })
+    }
+}
+
+fn apply_operator(op: Operator, left: &Value, right: &Value, span: &Span) -> Result<Value> {
+    match op {
+        Operator::Plus => left.plus(right, span),
+        Operator::Minus => left.minus(right, span),
+        Operator::Multiply => left.multiply(right, span),
+        Operator::Divide => left.divide(right, span),
+        Operator::Modulo => left.modulo(right, span),
+    }
+}
+
+/// Slots keyword arguments into their parameter positions after the
+/// positional ones, so the call can proceed as if all were positional.
+fn bind_keywords(
+    params: &[String],
+    args: Vec<Value>,
+    kwargs: Vec<(&str, Value)>,
+    span: &Span,
+) -> Result<Vec<Value>> {
+    let mut slots = args.into_iter().map(Some).collect::<Vec<_>>();
+    if slots.len() < params.len() {
+        slots.resize(params.len(), None);
+    }
+    for (name, value) in kwargs {
+        match params.iter().position(|param| param == name) {
+            Some(i) if slots[i].is_some() => error!(span, "Argument `{}` given more than once", name),
+            Some(i) => slots[i] = Some(value),
+            None => error!(span, "Unknown keyword argument `{}`", name),
+        }
+    }
+    // Only parameter slots can be empty, positional extras are always filled
+    let mut args = vec![];
+    for (i, slot) in slots.into_iter().enumerate() {
+        match slot {
+            Some(value) => args.push(value),
+            None => error!(span, "Missing argument `{}`", params[i]),
+        }
+    }
+    Ok(args)
+}
+
+fn compare(op: Comparison, left: &Value, right: &Value, span: &Span) -> Result<Value> {
+    match op {
+        Comparison::Equals => left.equals(right, span),
+        Comparison::NotEquals => left.not_equals(right, span),
+        Comparison::LessThan => left.less_than(right, span),
+        Comparison::GreaterThan => left.greater_than(right, span),
+        Comparison::LessEquals => left.less_equals(right, span),
+        Comparison::GreaterEquals => left.greater_equals(right, span),
+        Comparison::In => left.is_in(right, span),
+        Comparison::NotIn => left.not_in(right, span),
+    }
+}
+
+// Keeps tracebacks readable when a function was passed a large value
+fn truncate_repr(repr: String) -> String {
+    const MAX_LEN: usize = 40;
+    match repr.char_indices().nth(MAX_LEN) {
+        Some((i, _)) => format!("{}...", &repr[..i]),
+        None => repr,
     }
 }
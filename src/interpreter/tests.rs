@@ -0,0 +1,805 @@
+use super::Interpreter;
+use crate::error::Result;
+use crate::interpreter::value::Value;
+
+/// Runs a program and returns the value of its last statement.
+fn eval(source: &str) -> Result<Value> {
+    let tokens = crate::lexer::Lexer::new(source.to_string(), "<test>").lex()?;
+    let ast = crate::parser::Parser::new(tokens).parse()?;
+    Interpreter::new().execute(&ast)
+}
+
+/// The `repr` of the program's result, or the error message.
+fn run(source: &str) -> String {
+    match eval(source) {
+        Ok(value) => value.repr(),
+        Err(err) => format!("error: {}", err.message),
+    }
+}
+
+#[test]
+fn integer_division() {
+    assert_eq!(run("7 / 2"), "3");
+    assert_eq!(run("-7 / 2"), "-3");
+    assert_eq!(run("1 / 0"), "error: Division by zero");
+    assert_eq!(run("(-9223372036854775807 - 1) / -1"), "error: Integer overflow in division");
+}
+
+#[test]
+fn floor_division_rounds_down() {
+    assert_eq!(run("7 // 2"), "3");
+    assert_eq!(run("-7 // 2"), "-4");
+    assert_eq!(run("7 // -2"), "-4");
+    assert_eq!(run("-7 // -2"), "3");
+    assert_eq!(run("-8 // 2"), "-4");
+    assert_eq!(run("-7.5 // 2"), "-4");
+    assert_eq!(run("7 // 0"), "error: Division by zero");
+    // A trailing `//` is still floor division, never a comment.
+    assert_eq!(run("let half = 2\nlet x = 7 // half\nx"), "3");
+    assert_eq!(run("let x = 7 // half"), "error: Variable half not found");
+    assert_eq!(
+        run("(-9223372036854775807 - 1) // -1"),
+        "error: Integer overflow in floor division"
+    );
+}
+
+#[test]
+fn sum_of_ranges() {
+    assert_eq!(run("sum(0..5)"), "10");
+    assert_eq!(run("sum(5..0)"), "0");
+    assert_eq!(run("sum(range(1, 10, 3))"), "12");
+    assert_eq!(run("sum(range(10, 0, -3))"), "22");
+    assert_eq!(run("sum(0..5, 10)"), "20");
+    assert_eq!(run("sum(0..5, 0.5)"), "10.5");
+    assert_eq!(run("sum(0..1000000000)"), "499999999500000000");
+    assert_eq!(run("sum(0..9223372036854775807)"), "error: Integer overflow in sum()");
+    assert_eq!(run("sum([1, 2.5, 3])"), "6.5");
+}
+
+#[test]
+fn product_errors_name_the_type() {
+    assert_eq!(run("product(1..6)"), "120");
+    assert_eq!(
+        run("product([2, \"x\"])"),
+        "error: product() can only multiply numbers, got string"
+    );
+}
+
+#[test]
+fn match_runs_the_first_equal_arm() {
+    let program = |value: &str| {
+        format!(
+            "match {} {{\n\
+                 1 => \"one\", -2 => \"minus two\",\n\
+                 \"a\" => {{ \"letter\" }},\n\
+                 else => \"other\"\n\
+             }}",
+            value
+        )
+    };
+    assert_eq!(run(&program("1")), "\"one\"");
+    assert_eq!(run(&program("0 - 2")), "\"minus two\"");
+    assert_eq!(run(&program("\"a\"")), "\"letter\"");
+    assert_eq!(run(&program("3")), "\"other\"");
+    assert_eq!(run(&program("\"b\"")), "\"other\"");
+    assert_eq!(run("match 3 { 1 => 2 }"), "nothing");
+}
+
+#[test]
+fn match_arms_without_commas() {
+    let blocks = "match 0 - 2 {\n    1 => { \"one\" }\n    -2 => { \"neg\" }\n}";
+    assert_eq!(run(blocks), "\"neg\"");
+    assert_eq!(
+        run("match 1 {\n    1 => print(\"one\")\n    -2 => print(\"neg\")\n}"),
+        "error: Expected `,` or `}` after a match arm, but got FatArrow"
+    );
+}
+
+#[test]
+fn call_arguments_run_in_source_order() {
+    let prelude = "let order = \"\"\n\
+                   def mark(name, value) { order += name; value }\n\
+                   def f(a, b, c) { [a, b, c] }\n";
+    let program = |call: &str| format!("{}let result = [{}, order]\nresult", prelude, call);
+    assert_eq!(
+        run(&program("f(mark(\"a\", 1), mark(\"b\", 2), c=mark(\"c\", 3))")),
+        "[[1, 2, 3], \"abc\"]"
+    );
+    assert_eq!(
+        run(&program("f(mark(\"a\", 1), c=mark(\"c\", 3), b=mark(\"b\", 2))")),
+        "[[1, 2, 3], \"acb\"]"
+    );
+    assert_eq!(
+        run(&program("mark(\"f\", f)(mark(\"a\", 1), c=mark(\"c\", 3), b=mark(\"b\", 2))")),
+        "[[1, 2, 3], \"facb\"]"
+    );
+}
+
+/// Runs `f` on a stack sized the way `main` sizes it for the default
+/// recursion limit, since test threads are too small for deep recursion.
+fn with_interpreter_stack<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    std::thread::Builder::new()
+        .stack_size(super::stack_size(super::DEFAULT_MAX_CALL_DEPTH))
+        .spawn(f)
+        .unwrap()
+        .join()
+        .unwrap()
+}
+
+#[test]
+fn unbounded_recursion_is_a_runtime_error() {
+    let result = with_interpreter_stack(|| run("def forever(n) { forever(n + 1) }\nforever(0)"));
+    assert_eq!(result, "error: Maximum recursion depth exceeded");
+}
+
+#[test]
+fn recursion_limit_is_configurable() {
+    let source = "def depth(n) { if n == 0 { 0 } else { 1 + depth(n - 1) } }\ndepth(10)";
+    let (ok, err) = with_interpreter_stack(move || {
+        let tokens = crate::lexer::Lexer::new(source.to_string(), "<test>").lex().unwrap();
+        let ast = crate::parser::Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_call_depth(11);
+        let ok = interpreter.execute(&ast).map(|value| value.repr());
+        interpreter.set_max_call_depth(10);
+        let err = interpreter.execute(&ast).map_err(|err| err.message);
+        (ok.ok(), err.err())
+    });
+    assert_eq!(ok.as_deref(), Some("10"));
+    assert_eq!(err.as_deref(), Some("Maximum recursion depth exceeded"));
+}
+
+#[test]
+fn type_names() {
+    assert_eq!(run("type(1)"), "\"integer\"");
+    assert_eq!(run("type(1.5)"), "\"float\"");
+    assert_eq!(run("type(true)"), "\"boolean\"");
+    assert_eq!(run("type(\"a\")"), "\"string\"");
+    assert_eq!(run("type(print)"), "\"function\"");
+    assert_eq!(run("type(|x| { x })"), "\"function\"");
+    assert_eq!(run("type(enumerate([1]))"), "\"iterator\"");
+    assert_eq!(run("type(0..3)"), "\"range\"");
+    assert_eq!(run("type([1, 2])"), "\"list\"");
+    assert_eq!(run("type(bytes(\"a\"))"), "\"bytes\"");
+    assert_eq!(run("type(nothing)"), "\"nothing\"");
+}
+
+#[test]
+fn containers_agree_on_len_index_iteration_and_membership() {
+    // Each container with its items, which `len`, indexing, `for` and `in`
+    // must all agree on
+    let containers = [
+        ("\"abc\"", "[\"a\", \"b\", \"c\"]"),
+        ("[1, \"b\", 3.5]", "[1, \"b\", 3.5]"),
+        ("bytes(\"hi\")", "[104, 105]"),
+        ("2..5", "[2, 3, 4]"),
+        ("range(10, 0, -4)", "[10, 6, 2]"),
+        ("5..2", "[]"),
+    ];
+    for (container, items) in containers {
+        let program = format!(
+            "let c = {}\n\
+             let n = len(c)\n\
+             let by_for = list(0..n)\n\
+             let i = 0\n\
+             for x in c {{ by_for[i] = x; i += 1 }}\n\
+             let by_index = list(0..n)\n\
+             for i in 0..n {{ by_index[i] = c[i] }}\n\
+             let all_in = true\n\
+             for x in c {{ all_in = all_in and x in c }}\n\
+             let result = [by_for, by_index, all_in]\n\
+             result",
+            container
+        );
+        assert_eq!(run(&program), format!("[{}, {}, true]", items, items), "{}", container);
+        assert_eq!(
+            run(&format!("let c = {}\nc[len(c)]", container)),
+            "error: Index out of bounds",
+            "{}",
+            container
+        );
+    }
+}
+
+#[test]
+fn negative_based_literals() {
+    assert_eq!(run("-0xFF == -255"), "true");
+    assert_eq!(run("-0b10 == -2"), "true");
+    assert_eq!(run("-0o17 == -15"), "true");
+    assert_eq!(run("-0x_7fff_ffff_ffff_ffff"), "-9223372036854775807");
+}
+
+#[test]
+fn recursive_functions_see_themselves() {
+    let results = with_interpreter_stack(|| {
+        [
+            // `return` in the base case's block must stop the rest of the body
+            run("def fact(n) {\n if n <= 1 { return 1 }\n return n * fact(n - 1)\n}\nfact(10)"),
+            run("let fib = |n| { if n < 2 { return n }\n fib(n - 1) + fib(n - 2) }\nfib(15)"),
+            run("def count(n) {\n while true {\n if n == 0 { return \"done\" }\n return count(n - 1)\n }\n}\n\
+                 count(5)"),
+        ]
+    });
+    assert_eq!(results, ["3628800", "610", "\"done\""]);
+}
+
+#[test]
+fn string_repetition_and_concatenation() {
+    assert_eq!(run("\"ab\" * 3"), "\"ababab\"");
+    assert_eq!(run("\"ab\" * 0"), "\"\"");
+    assert_eq!(run("\"ab\" + \"cd\""), "\"abcd\"");
+    assert_eq!(run("\"ab\" * -1"), "error: -1 is not a positive integer.");
+    assert_eq!(
+        run("\"ab\" * 9223372036854775807"),
+        "error: String repetition result is too large"
+    );
+}
+
+#[test]
+fn zero_slice_step_is_rejected_for_every_sliceable() {
+    assert_eq!(run("\"abc\"[::0]"), "error: Step cannot be 0");
+    assert_eq!(run("[1, 2, 3][::0]"), "error: Step cannot be 0");
+}
+
+#[test]
+fn percent_formats_strings() {
+    assert_eq!(run("\"%d apples\" % 3"), "\"3 apples\"");
+    assert_eq!(run("\"%s and %s\" % [\"x\", 2]"), "\"x and 2\"");
+    assert_eq!(run("\"100%%\" % []"), "\"100%\"");
+    assert_eq!(run("\"%s %s\" % [1]"), "error: Not enough arguments for format string");
+    assert_eq!(run("\"%s\" % [1, 2]"), "error: Too many arguments for format string");
+}
+
+#[test]
+fn slicing_strings_by_character() {
+    assert_eq!(run("\"héllo wörld\"[1:8]"), "\"éllo wö\"");
+    assert_eq!(run("\"日本語\"[::-1]"), "\"語本日\"");
+    assert_eq!(run("let s = \"é\" * 100000\nlen(s[50000:])"), "50000");
+}
+
+#[test]
+fn take_skip_and_chain() {
+    assert_eq!(run("list(take(0..100, 3))"), "[0, 1, 2]");
+    assert_eq!(run("list(skip(0..5, 3))"), "[3, 4]");
+    assert_eq!(run("list(take(skip(0..9223372036854775807, 5), 2))"), "[5, 6]");
+    assert_eq!(run("list(chain(0..2, [\"a\", \"b\"]))"), "[0, 1, \"a\", \"b\"]");
+}
+
+#[test]
+fn group_digits_inserts_separators() {
+    assert_eq!(run("group_digits(1234567) == \"1,234,567\""), "true");
+    assert_eq!(run("group_digits(-1234567)"), "\"-1,234,567\"");
+    assert_eq!(run("group_digits(123)"), "\"123\"");
+}
+
+#[test]
+fn approx_eq_tolerates_rounding() {
+    assert_eq!(run("approx_eq(0.1 + 0.2, 0.3)"), "true");
+    assert_eq!(run("approx_eq(0.1, 0.2)"), "false");
+}
+
+#[test]
+fn capitalize_and_title() {
+    assert_eq!(run("capitalize(\"hELLO\") == \"Hello\""), "true");
+    assert_eq!(run("title(\"hello world\") == \"Hello World\""), "true");
+}
+
+#[test]
+fn dbg_returns_its_argument() {
+    assert_eq!(run("let x = dbg(1 + 2)\nx * 2"), "6");
+}
+
+#[test]
+fn division_result_types() {
+    assert_eq!(run("5 / 2"), "2");
+    assert_eq!(run("5 // 2"), "2");
+    assert_eq!(run("5.0 / 2"), "2.5");
+    assert_eq!(run("5 / 2.0"), "2.5");
+}
+
+#[test]
+fn product_of_lists_and_ranges() {
+    assert_eq!(run("product([1, 2, 3, 4]) == 24"), "true");
+    assert_eq!(run("product([])"), "1");
+    assert_eq!(run("product(0..1000000000)"), "0");
+    assert_eq!(run("product(1..100)"), "error: Integer overflow in product()");
+}
+
+#[test]
+fn arrays_are_shared_by_reference() {
+    let prelude = "def fill(a) { a[0] = 99 }\nlet xs = [1, 2]\n";
+    assert_eq!(run(&format!("{}fill(xs)\nxs", prelude)), "[99, 2]");
+    assert_eq!(run(&format!("{}let ys = xs\nfill(ys)\nxs", prelude)), "[99, 2]");
+    assert_eq!(run(&format!("{}fill(copy(xs))\nxs", prelude)), "[1, 2]");
+}
+
+#[test]
+fn for_each_over_a_mixed_list() {
+    let program = "let out = \"\"\nfor x in [1, \"a\", 2, \"b\"] { out += str(x) }\nout";
+    assert_eq!(run(program), "\"1a2b\"");
+}
+
+#[test]
+fn replace_first_and_trims() {
+    assert_eq!(run("replace_first(\"a-b-c\", \"-\", \"+\")"), "\"a+b-c\"");
+    assert_eq!(run("replace_first(\"abc\", \"x\", \"y\")"), "\"abc\"");
+    assert_eq!(run("trim_prefix(\"prefix.rs\", \"prefix\")"), "\".rs\"");
+    assert_eq!(run("trim_prefix(\"main.rs\", \"prefix\")"), "\"main.rs\"");
+    assert_eq!(run("trim_suffix(\"main.rs\", \".rs\")"), "\"main\"");
+    assert_eq!(run("trim_suffix(\"main.rs\", \".py\")"), "\"main.rs\"");
+}
+
+#[test]
+fn abort_unwinds_with_a_trace() {
+    let (is_abort, message, frames) = with_interpreter_stack(|| {
+        let err = eval("def inner(x) { abort(\"stop\") }\ndef outer() { inner(1) }\nouter()")
+            .unwrap_err();
+        let frames: Vec<_> = err.trace.iter().map(|frame| frame.name.clone()).collect();
+        (matches!(err.kind, crate::error::ErrorKind::Abort), err.message, frames)
+    });
+    assert!(is_abort);
+    assert_eq!(message, "stop");
+    assert_eq!(frames, ["inner", "outer"]);
+}
+
+#[test]
+fn power_is_right_associative() {
+    assert_eq!(run("2 ** 3 ** 2 == 512"), "true");
+    assert_eq!(run("-2 ** 2"), "-4");
+}
+
+#[test]
+fn negating_expressions() {
+    assert_eq!(run("-(2 + 3)"), "-5");
+    assert_eq!(run("let x = 1.5; -x * 2"), "-3");
+    assert_eq!(run("-\"a\""), "error: Invalid type for negation");
+}
+
+#[test]
+fn and_or_short_circuit() {
+    let prelude = "def boom() { abort(\"evaluated\") }\n";
+    assert_eq!(run(&format!("{}false and boom()", prelude)), "false");
+    assert_eq!(run(&format!("{}true or boom()", prelude)), "true");
+    assert_eq!(run(&format!("{}true and boom()", prelude)), "error: evaluated");
+}
+
+#[test]
+fn del_removes_bindings_and_elements() {
+    assert_eq!(run("let x = 1\ndel x\nx"), "error: Variable x not found");
+    assert_eq!(run("let a = [1, 2, 3]\ndel a[1]\na"), "[1, 3]");
+    assert_eq!(run("del y"), "error: Variable y not found");
+    assert_eq!(run("let a = [1]\ndel a[3]"), "error: Index out of bounds");
+}
+
+#[test]
+fn bytes_round_trip() {
+    assert_eq!(run("decode(bytes(\"héllo\"))"), "\"héllo\"");
+    assert_eq!(run("bytes([104, 105])"), "b\"hi\"");
+    assert_eq!(
+        run("decode(bytes([255]))"),
+        "error: decode() got invalid UTF-8: invalid utf-8 sequence of 1 bytes from index 0"
+    );
+}
+
+#[test]
+fn compound_assignment_evaluates_the_index_once() {
+    let program = "let a = [1, 2]\n\
+                   let calls = 0\n\
+                   def idx() { calls += 1; 1 }\n\
+                   a[idx()] += 5\n\
+                   let result = [a, calls]\n\
+                   result";
+    assert_eq!(run(program), "[[1, 7], 1]");
+}
+
+#[test]
+fn list_slices() {
+    assert_eq!(run("[1, 2, 3, 4, 5][::2]"), "[1, 3, 5]");
+    assert_eq!(run("[1, 2, 3, 4, 5][::-1]"), "[5, 4, 3, 2, 1]");
+    assert_eq!(run("[1, 2][5:]"), "[]");
+}
+
+#[test]
+fn traces_show_argument_values() {
+    let frames = with_interpreter_stack(|| {
+        let err = eval("def half(n, label) { n / 0 }\nhalf(7, \"x\")").unwrap_err();
+        let frame = &err.trace[0];
+        (frame.name.clone(), frame.args.clone())
+    });
+    assert_eq!(
+        frames,
+        (
+            "half".to_string(),
+            vec![
+                ("n".to_string(), "7".to_string()),
+                ("label".to_string(), "\"x\"".to_string())
+            ]
+        )
+    );
+}
+
+#[test]
+fn traces_show_arguments_as_called() {
+    let args = with_interpreter_stack(|| {
+        let source = "def f(n, *rest) { n = n + 100; rest = nothing; let y = 1 / 0 }\nf(5, 6)";
+        eval(source).unwrap_err().trace[0].args.clone()
+    });
+    let args: Vec<_> = args.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+    assert_eq!(args, ["n=5", "rest=[6]"]);
+}
+
+#[test]
+fn negative_slice_bounds() {
+    assert_eq!(run("\"hello\"[-3:-1]"), "\"ll\"");
+    assert_eq!(run("\"hello\"[::-1]"), "\"olleh\"");
+    assert_eq!(run("\"hello\"[-2:]"), "\"lo\"");
+    assert_eq!(run("\"hello\"[3:0:-1]"), "\"lle\"");
+}
+
+#[test]
+fn index_assignment_mutates_in_place() {
+    assert_eq!(run("let a = [1, 2, 3]\na[1] = 20\na"), "[1, 20, 3]");
+    assert_eq!(run("let b = bytes(\"hi\")\nb[0] = 72\nb"), "b\"Hi\"");
+    assert_eq!(run("let a = [1]\na[1] = 2"), "error: Index out of bounds");
+    assert_eq!(run("let s = \"abc\"\ns[0] = \"x\""), "error: Can't assign to index 0 of abc");
+}
+
+#[test]
+fn sum_min_max_stream_their_input() {
+    // Materializing the whole range would need far more memory than exists
+    let huge = "take(0..9223372036854775807, 100000)";
+    assert_eq!(run(&format!("sum({})", huge)), "4999950000");
+    assert_eq!(run(&format!("max({})", huge)), "99999");
+    assert_eq!(run(&format!("min(skip({}, 5))", huge)), "5");
+}
+
+#[test]
+fn escaping_builtins() {
+    assert_eq!(run("shell_quote(\"plain/path.txt\")"), "\"plain/path.txt\"");
+    assert_eq!(run("shell_quote(\"a b\")"), "\"'a b'\"");
+    assert_eq!(run("shell_quote(\"it's\")"), "\"'it'\"'\"'s'\"");
+    assert_eq!(run("shell_quote(\"\")"), "\"''\"");
+    assert_eq!(
+        run("html_escape(\"<a href=\\\"x\\\">&'</a>\")"),
+        "\"&lt;a href=&quot;x&quot;&gt;&amp;&#39;&lt;/a&gt;\""
+    );
+    assert_eq!(run("url_encode(\"a b&c=d/é?\")"), "\"a%20b%26c%3Dd%2F%C3%A9%3F\"");
+    assert_eq!(run("url_encode(\"A-z_0.9~\")"), "\"A-z_0.9~\"");
+}
+
+#[test]
+fn f_strings() {
+    assert_eq!(run("let x = 2\nf\"{x} squared is {x * x}\""), "\"2 squared is 4\"");
+    assert_eq!(run("f\"{{literal}} {[1, 2][1]}\""), "\"{literal} 2\"");
+    assert_eq!(run("f\"{\"in\" + f\"{1 + 1}\"}\""), "\"in2\"");
+}
+
+#[test]
+fn str_conversions() {
+    assert_eq!(run("str(1)"), "\"1\"");
+    assert_eq!(run("str(1.5)"), "\"1.5\"");
+    assert_eq!(run("str(true)"), "\"true\"");
+    assert_eq!(run("str(nothing)"), "\"nothing\"");
+    assert_eq!(run("str(\"s\")"), "\"s\"");
+}
+
+#[test]
+fn int_and_float_conversions() {
+    assert_eq!(run("int(\"42\")"), "42");
+    assert_eq!(run("int(-3.9)"), "-3");
+    assert_eq!(run("int(true)"), "1");
+    assert_eq!(run("float(\"1.5\")"), "1.5");
+    assert_eq!(run("type(float(2))"), "\"float\"");
+    assert_eq!(run("int(\"x\")"), "error: int() can't parse \"x\" as an integer");
+    assert_eq!(run("float(\"x\")"), "error: float() can't parse \"x\" as a float");
+}
+
+#[test]
+fn len_of_ranges() {
+    assert_eq!(run("len(0..0)"), "0");
+    assert_eq!(run("len(5..0)"), "0");
+    assert_eq!(run("len(range(5, 0, -1))"), "5");
+    assert_eq!(run("len((0 - 9223372036854775807)..9223372036854775807)"), "9223372036854775807");
+}
+
+#[test]
+fn abs_min_max() {
+    assert_eq!(run("abs(-3)"), "3");
+    assert_eq!(run("abs(-2.5)"), "2.5");
+    assert_eq!(run("max(1, 2, 3)"), "3");
+    assert_eq!(run("min([5, 2, 9])"), "2");
+    assert_eq!(run("abs(0 - 9223372036854775807 - 1)"), "error: Integer overflow in abs()");
+}
+
+#[test]
+fn range_builtin() {
+    assert_eq!(run("list(range(3))"), "[0, 1, 2]");
+    assert_eq!(run("list(range(1, 4))"), "[1, 2, 3]");
+    assert_eq!(run("list(range(0, 10, 3))"), "[0, 3, 6, 9]");
+    assert_eq!(run("list(range(5, 0, -2))"), "[5, 3, 1]");
+    assert_eq!(run("range(0, 5, 0)"), "error: range() step cannot be 0");
+}
+
+#[test]
+fn enumerate_pairs_indices_with_items() {
+    assert_eq!(run("list(enumerate(\"abc\"))"), "[[0, \"a\"], [1, \"b\"], [2, \"c\"]]");
+}
+
+#[test]
+fn map_and_filter() {
+    assert_eq!(run("list(map(|x| { x * x }, 0..4))"), "[0, 1, 4, 9]");
+    assert_eq!(run("list(filter(|x| { x % 2 == 0 }, 0..5))"), "[0, 2, 4]");
+}
+
+#[test]
+fn string_builtins() {
+    assert_eq!(run("upper(\"aB\")"), "\"AB\"");
+    assert_eq!(run("lower(\"aB\")"), "\"ab\"");
+    assert_eq!(run("strip(\"  x \")"), "\"x\"");
+    assert_eq!(run("split(\"a,b,c\", \",\")"), "[\"a\", \"b\", \"c\"]");
+    assert_eq!(run("join(\",\", split(\"a,b,c\", \",\"))"), "\"a,b,c\"");
+}
+
+#[test]
+fn ord_and_chr() {
+    assert_eq!(run("ord(\"A\") == 65"), "true");
+    assert_eq!(run("chr(97) == \"a\""), "true");
+    assert_eq!(run("chr(-1)"), "error: chr() got an invalid code point -1");
+}
+
+#[test]
+fn math_builtins() {
+    assert_eq!(run("sqrt(16)"), "4");
+    assert_eq!(run("type(sqrt(16))"), "\"float\"");
+    assert_eq!(run("floor(2.5)"), "2");
+    assert_eq!(run("ceil(2.1)"), "3");
+    assert_eq!(run("round(2.5)"), "3");
+    assert_eq!(run("pow(2, 10)"), "1024");
+    assert_eq!(run("sqrt(-1)"), "error: sqrt() of negative number -1");
+}
+
+#[test]
+fn string_methods() {
+    assert_eq!(run("\"a,b\".split(\",\")"), "[\"a\", \"b\"]");
+    assert_eq!(run("\"  x \".strip().upper()"), "\"X\"");
+    assert_eq!(run("\",\".join([\"a\", \"b\"])"), "\"a,b\"");
+    assert_eq!(run("\"abc\".nope()"), "error: No method `nope` on type string");
+}
+
+#[test]
+fn break_outside_loops_is_an_error() {
+    assert_eq!(run("break"), "error: `break` outside of loop");
+    assert_eq!(run("continue"), "error: `continue` outside of loop");
+    assert_eq!(run("def f() { break }\nf()"), "error: `break` outside of loop");
+    assert_eq!(run("for x in 0..3 { def f() { break }\nf() }"), "error: `break` outside of loop");
+}
+
+#[test]
+fn control_flow_does_not_cross_calls() {
+    let program = "def first(xs) { for x in xs { return x } }\n\
+                   let seen = 0\n\
+                   for i in 0..3 { first([i]); seen += 1 }\n\
+                   seen";
+    assert_eq!(run(program), "3");
+    assert_eq!(
+        run("def f() { continue }\nfor i in 0..3 { f() }"),
+        "error: `continue` outside of loop"
+    );
+}
+
+#[test]
+fn multiple_assignment_evaluates_values_first() {
+    assert_eq!(run("let a = 1; let b = 2\na, b = b, a\nlet result = [a, b]\nresult"), "[2, 1]");
+    assert_eq!(run("let p, q = 5, 6\np * q"), "30");
+    assert_eq!(run("let [x, y] = [3, 4]\nx - y"), "-1");
+}
+
+#[test]
+fn c_style_for_loop_counts() {
+    assert_eq!(run("let s = 0\nfor (let i = 0; i < 5; i += 1) { s += i }\ns"), "10");
+    assert_eq!(run("let n = 0\nfor (; n < 3;) { n += 1 }\nn"), "3");
+}
+
+#[test]
+fn both_for_loop_forms() {
+    let program = |header: &str| format!("let s = \"\"\nfor {} {{ s += str(i) }}\ns", header);
+    assert_eq!(run(&program("i in 0..4")), "\"0123\"");
+    assert_eq!(run(&program("(let i = 0; i < 4; i += 1)")), "\"0123\"");
+}
+
+#[test]
+fn prefix_and_postfix_increments() {
+    assert_eq!(run("let n = 1\nlet r = [n++, n]\nr"), "[1, 2]");
+    assert_eq!(run("let n = 1\nlet r = [++n, n]\nr"), "[2, 2]");
+    assert_eq!(run("let n = 1\nlet r = [n--, --n]\nr"), "[1, -1]");
+    assert_eq!(run("let a = [1]\nlet old = a[0]++\nlet r = [old, a]\nr"), "[1, [2]]");
+}
+
+#[test]
+fn assert_messages() {
+    assert_eq!(run("assert 1 == 2"), "error: Assertion failed");
+    assert_eq!(run("assert 1 == 2, \"nope\""), "error: Assertion failed: nope");
+    assert_eq!(run("assert 1 == 1, \"fine\""), "nothing");
+}
+
+#[test]
+fn format_substitutes_placeholders() {
+    assert_eq!(run("format(\"{} + {} = {}\", 1, 2, 1 + 2)"), "\"1 + 2 = 3\"");
+    assert_eq!(
+        run("format(\"{} {}\", 1)"),
+        "error: format() template has 2 placeholders, but got 1 arguments"
+    );
+}
+
+#[test]
+fn variadic_arguments() {
+    let prelude = "def total(first, *rest) { first + sum(rest) }\n";
+    assert_eq!(run(&format!("{}total(1, 2, 3, 4)", prelude)), "10");
+    assert_eq!(run(&format!("{}total(1)", prelude)), "1");
+    assert_eq!(run("def f(*args) { args }\nf(1, \"a\")"), "[1, \"a\"]");
+}
+
+#[test]
+fn membership_in_each_container() {
+    assert_eq!(run("\"ell\" in \"hello\""), "true");
+    assert_eq!(run("\"z\" not in \"hello\""), "true");
+    assert_eq!(run("1 in \"hello\""), "error: Can only search for strings in a string");
+    assert_eq!(run("2 in [1, 2]"), "true");
+    assert_eq!(run("[1] in [[1], 2]"), "true");
+    assert_eq!(run("104 in bytes(\"hi\")"), "true");
+    assert_eq!(run("300 in bytes(\"hi\")"), "false");
+    assert_eq!(run("6 in range(0, 10, 3)"), "true");
+    assert_eq!(run("7 in range(0, 10, 3)"), "false");
+    assert_eq!(run("1 in 5..0"), "false");
+    assert_eq!(run("\"a\" in 0..3"), "false");
+    assert_eq!(run("1 in 5"), "error: Can't check membership in integer");
+}
+
+#[test]
+fn numeric_literal_values() {
+    assert_eq!(run("1_000"), "1000");
+    assert_eq!(run("1.5e3"), "1500");
+    assert_eq!(run("2e-2"), "0.02");
+    assert_eq!(run("1_000.000_5"), "1000.0005");
+}
+
+#[test]
+fn iterating_non_ascii_strings() {
+    assert_eq!(run("list(\"héllo\")"), "[\"h\", \"é\", \"l\", \"l\", \"o\"]");
+    assert_eq!(run("let n = 0\nfor c in \"é\" * 100000 { n += 1 }\nn"), "100000");
+}
+
+#[test]
+fn indexing_counts_characters() {
+    assert_eq!(run("len(\"héllo 👋\")"), "7");
+    assert_eq!(run("\"héllo 👋\"[1]"), "\"é\"");
+    assert_eq!(run("\"héllo 👋\"[6]"), "\"👋\"");
+    assert_eq!(run("\"👋\"[1]"), "error: Index out of bounds");
+}
+
+#[test]
+fn repeated_slicing_of_a_large_string() {
+    let program = "let s = \"abcdé\" * 20000\n\
+                   let n = 0\n\
+                   for i in 0..200 { n += len(s[i:i + 10]) }\n\
+                   n";
+    assert_eq!(run(program), "2000");
+}
+
+#[test]
+fn bitwise_masking_and_shifting() {
+    assert_eq!(run("0xABCD & 0xFF"), "205");
+    assert_eq!(run("0xF0 | 0x0F"), "255");
+    assert_eq!(run("6 ^ 3"), "5");
+    assert_eq!(run("~5"), "-6");
+    assert_eq!(run("1 << 10"), "1024");
+    assert_eq!(run("-8 >> 1"), "-4");
+    assert_eq!(run("(0xABCD >> 8) & 0xFF"), "171");
+    assert_eq!(run("1 | 2 == 3"), "true");
+    assert_eq!(run("1 << -1"), "error: Negative shift amount");
+    assert_eq!(run("1.5 & 1"), "error: Bitwise operators only support integers");
+}
+
+#[test]
+fn adding_to_strings_stringifies() {
+    assert_eq!(run("\"x\" + 1"), "\"x1\"");
+    assert_eq!(run("1 + \"x\""), "\"1x\"");
+    assert_eq!(run("\"x\" + [1, 2]"), "\"x[1, 2]\"");
+}
+
+#[test]
+fn ranges_and_lists_compare_structurally() {
+    assert_eq!(run("0..3 == 0..3"), "true");
+    assert_eq!(run("0..3 == 0..4"), "false");
+    assert_eq!(run("range(0, 6, 2) == range(0, 6, 3)"), "false");
+    assert_eq!(run("[1, [2, \"a\"]] == [1, [2, \"a\"]]"), "true");
+    assert_eq!(run("[1, [2, \"a\"]] == [1, [2, \"b\"]]"), "false");
+}
+
+#[test]
+fn reversing() {
+    assert_eq!(run("list(reversed(\"abc\"))"), "[\"c\", \"b\", \"a\"]");
+    assert_eq!(run("list(reversed(0..3))"), "[2, 1, 0]");
+    assert_eq!(run("let a = [1, 2, 3]\na.reverse()\na"), "[3, 2, 1]");
+}
+
+#[test]
+fn sum_builtin() {
+    assert_eq!(run("sum(0..5) == 10"), "true");
+    assert_eq!(run("sum([1, 2.5]) == 3.5"), "true");
+    assert_eq!(run("sum([1, 2], 10)"), "13");
+    assert_eq!(run("sum([1, \"a\"])"), "error: sum() can only add numbers, got string");
+    assert_eq!(
+        run("sum([1], \"a\")"),
+        "error: sum() start value must be a number, got string"
+    );
+}
+
+#[test]
+fn reduce_folds_items() {
+    assert_eq!(run("reduce(|a, b| => a * b, 1..5, 1)"), "24");
+    assert_eq!(run("reduce(|a, b| => a + b, [\"a\", \"b\"], \"\")"), "\"ab\"");
+}
+
+#[test]
+fn closures_share_captured_variables() {
+    let program = "def make_counter() {\n\
+                       let count = 0\n\
+                       return || => ++count\n\
+                   }\n\
+                   let a = make_counter()\n\
+                   let b = make_counter()\n\
+                   a(); a()\n\
+                   let result = [a(), b()]\n\
+                   result";
+    assert_eq!(with_interpreter_stack(move || run(program)), "[3, 1]");
+    assert_eq!(run("(|x| => x * 2)(21)"), "42");
+}
+
+#[test]
+fn nothing_equals_only_nothing() {
+    assert_eq!(run("nothing == nothing"), "true");
+    assert_eq!(run("5 != nothing"), "true");
+    assert_eq!(run("def f() { }\nf() == nothing"), "true");
+}
+
+#[test]
+fn functions_return_their_last_expression() {
+    assert_eq!(run("def add(a, b) { a + b }\nadd(2, 3)"), "5");
+    assert_eq!(run("def pick(x) { if x { \"yes\" } else { \"no\" } }\npick(false)"), "\"no\"");
+    assert_eq!(run("def early(x) { if x { return 1 }\n2 }\nearly(true)"), "1");
+}
+
+#[test]
+fn chained_comparisons() {
+    assert_eq!(run("let x = 5\n0 < x < 10"), "true");
+    assert_eq!(run("let x = 15\n0 < x < 10"), "false");
+    assert_eq!(run("1 < 2 <= 2 != 3"), "true");
+    let program = "let calls = 0\n\
+                   def mid() { calls += 1; 5 }\n\
+                   let ok = 0 < mid() < 10\n\
+                   let result = [ok, calls]\n\
+                   result";
+    assert_eq!(run(program), "[true, 1]");
+    // A false link stops the chain before later operands run
+    assert_eq!(run("def boom() { abort(\"evaluated\") }\n2 < 1 < boom()"), "false");
+}
+
+
+#[test]
+fn increment_overflow_is_an_error() {
+    let overflow = "error: Integer overflow in increment";
+    assert_eq!(run("let x = 9223372036854775807\nx++"), overflow);
+    assert_eq!(run("let x = -9223372036854775807 - 1\nx--"), overflow);
+    assert_eq!(run("let xs = [9223372036854775807]; ++xs[0]"), overflow);
+}
+
+
+#[test]
+fn range_indexing_near_the_integer_limits() {
+    let wide = "range(-9223372036854775807 - 1, 9223372036854775807, 4611686018427387904)";
+    assert_eq!(run(&format!("{}[3]", wide)), "4611686018427387904");
+    assert_eq!(run(&format!("{}[4]", wide)), "error: Index out of bounds");
+    let down = "range(9223372036854775807, -9223372036854775807, -4611686018427387904)";
+    assert_eq!(run(&format!("{}[3]", down)), "-4611686018427387905");
+}
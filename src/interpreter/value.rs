@@ -9,7 +9,8 @@ pub struct IteratorValue(pub Ref<dyn Iterator<Item = Value>>);
 
 struct StringIterator {
     string: Ref<String>,
-    index: usize,
+    /// Byte offset of the next character
+    offset: usize,
 }
 
 impl Iterator for StringIterator {
@@ -17,13 +18,9 @@ impl Iterator for StringIterator {
 
     fn next(&mut self) -> Option<Value> {
         let string = self.string.borrow();
-        if self.index >= string.len() {
-            None
-        } else {
-            let c = string.chars().nth(self.index).unwrap();
-            self.index += 1;
-            Some(Value::String(make!(c.to_string())))
-        }
+        let c = string.get(self.offset..)?.chars().next()?;
+        self.offset += c.len_utf8();
+        Some(Value::String(make!(c.to_string())))
     }
 }
 
@@ -49,16 +46,48 @@ impl Iterator for ArrayIterator {
 
 impl IteratorValue {
     pub fn for_string(string: Ref<String>) -> IteratorValue {
-        IteratorValue(make!(StringIterator { string, index: 0 }))
+        IteratorValue(make!(StringIterator { string, offset: 0 }))
     }
 
-    pub fn for_range(start: &i64, end: &i64) -> IteratorValue {
-        IteratorValue(make!((*start..*end).map(Value::Integer)))
+    pub fn for_range(start: i64, end: i64, step: i64) -> IteratorValue {
+        IteratorValue(make!(range_items(start, end, step).map(Value::Integer)))
     }
 
     pub fn for_array(array: Ref<Vec<Value>>) -> IteratorValue {
         IteratorValue(make!(ArrayIterator { array, index: 0 }))
     }
+
+    pub fn for_bytes(bytes: Ref<Vec<u8>>) -> IteratorValue {
+        let bytes = bytes.borrow().clone();
+        IteratorValue(make!(bytes.into_iter().map(|b| Value::Integer(b as i64))))
+    }
+
+    pub fn take(self, n: i64) -> IteratorValue {
+        IteratorValue(make!(Iterator::take(self, n.max(0) as usize)))
+    }
+
+    pub fn skip(self, n: i64) -> IteratorValue {
+        IteratorValue(make!(Iterator::skip(self, n.max(0) as usize)))
+    }
+
+    /// Yields `[index, item]` pairs.
+    pub fn enumerate(self) -> IteratorValue {
+        IteratorValue(make!(Iterator::enumerate(self).map(|(i, item)| {
+            Value::Array(make!(vec![Value::Integer(i as i64), item]))
+        })))
+    }
+
+    pub fn chain(iters: Vec<IteratorValue>) -> IteratorValue {
+        IteratorValue(make!(iters.into_iter().flatten()))
+    }
+}
+
+impl Iterator for IteratorValue {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        self.0.borrow_mut().next()
+    }
 }
 
 impl std::fmt::Debug for IteratorValue {
@@ -72,6 +101,7 @@ pub struct Function {
     pub name: String,
     pub body: Rc<AST>,
     pub args: Vec<String>,
+    pub variadic: Option<String>,
     pub scope: Ref<Scope>,
 }
 
@@ -84,8 +114,14 @@ pub enum Value {
     BuiltInFunction(Ref<String>),
     Function(Ref<Function>),
     Iterator(IteratorValue),
-    Range(i64, i64),
+    /// Start, end (exclusive) and a non-zero step, which counts down when
+    /// negative. `a..b` always has a step of 1.
+    Range(i64, i64, i64),
+    /// Arrays are shared by reference: assigning one or passing it to a
+    /// function doesn't copy it, so mutations are visible through every
+    /// binding. Use the `copy` builtin to get an independent array.
     Array(Ref<Vec<Value>>),
+    Bytes(Ref<Vec<u8>>),
     Nothing,
 }
 
@@ -98,7 +134,7 @@ impl std::fmt::Debug for Value {
             Value::Boolean(boolean) => write!(f, "{}", boolean),
             Value::Nothing => write!(f, "nothing"),
             Value::Iterator(_) => write!(f, "<iterator>"),
-            Value::Range(start, end) => write!(f, "{}..{}", start, end),
+            Value::Range(..) => write!(f, "{}", self.repr()),
             Value::BuiltInFunction(name) => write!(f, "<builtin {}>", name.borrow()),
             Value::Function(func) => {
                 let func = func.borrow();
@@ -114,6 +150,7 @@ impl std::fmt::Debug for Value {
                 }
                 write!(f, "]")
             }
+            Value::Bytes(_) => write!(f, "{}", self.repr()),
         }
     }
 }
@@ -136,12 +173,17 @@ impl std::cmp::PartialEq for Value {
                     left.iter().zip(right.iter()).all(|(a, b)| a == b)
                 }
             },
+            (Value::Bytes(left), Value::Bytes(right)) => *left.borrow() == *right.borrow(),
+            (Value::Range(a, b, c), Value::Range(x, y, z)) => (a, b, c) == (x, y, z),
+            (Value::Nothing, Value::Nothing) => true,
             _ => false,
         }
     }
 }
 
 impl Value {
+    /// If exactly one operand is a string, the other is converted as `str()`
+    /// would, so `"count: " + 5` is `"count: 5"`. Numbers still only add.
     pub fn plus(&self, other: &Value, span: &Span) -> Result<Value> {
         Ok(match (self, other) {
             (Value::Integer(left), Value::Integer(right)) => Value::Integer(*left + *right),
@@ -149,6 +191,8 @@ impl Value {
             (Value::Float(left), Value::Float(right)) => Value::Float(*left + *right),
             (Value::Float(left), Value::Integer(right)) => Value::Float(*left + *right as f64),
             (Value::String(left), Value::String(right)) => Value::String(make!(left.borrow().clone() + get!(right))),
+            (Value::String(left), right) => Value::String(make!(format!("{}{}", left.borrow(), right))),
+            (left, Value::String(right)) => Value::String(make!(format!("{}{}", left, right.borrow()))),
             _ => error!(span, "Invalid types for addition"),
         })
     }
@@ -173,15 +217,31 @@ impl Value {
                 if *right < 0 {
                     error!(span, "{right} is not a positive integer.")
                 }
-                Value::String(make!(left.borrow().repeat(*right as usize)))
+                let left = left.borrow();
+                match left.len().checked_mul(*right as usize) {
+                    Some(len) if len <= isize::MAX as usize => {}
+                    _ => error!(span, "String repetition result is too large"),
+                }
+                Value::String(make!(left.repeat(*right as usize)))
             }
             _ => error!(span, "Invalid types for multiplication"),
         })
     }
 
+    /// Dividing two integers is integer division truncating toward zero, so
+    /// `5 / 2 == 2` and `-5 / 2 == -2`. If either operand is a float the
+    /// result is a float: `5 / 2.0 == 2.5`.
     pub fn divide(&self, other: &Value, span: &Span) -> Result<Value> {
         Ok(match (self, other) {
-            (Value::Integer(left), Value::Integer(right)) => Value::Integer(*left / *right),
+            (Value::Integer(left), Value::Integer(right)) => {
+                if *right == 0 {
+                    error!(span, "Division by zero")
+                }
+                match left.checked_div(*right) {
+                    Some(res) => Value::Integer(res),
+                    None => error!(span, "Integer overflow in division"),
+                }
+            }
             (Value::Integer(left), Value::Float(right)) => Value::Float(*left as f64 / *right),
             (Value::Float(left), Value::Float(right)) => Value::Float(*left / *right),
             (Value::Float(left), Value::Integer(right)) => Value::Float(*left / *right as f64),
@@ -189,6 +249,87 @@ impl Value {
         })
     }
 
+    /// Rounds toward negative infinity (unlike `/`, which truncates), so
+    /// `-7 // 2 == -4`. Float operands give a floored integer.
+    pub fn floor_divide(&self, other: &Value, span: &Span) -> Result<Value> {
+        Ok(match (self, other) {
+            (Value::Integer(left), Value::Integer(right)) => {
+                if *right == 0 {
+                    error!(span, "Division by zero")
+                }
+                let Some(quotient) = left.checked_div(*right) else {
+                    error!(span, "Integer overflow in floor division")
+                };
+                if left % right != 0 && (*left < 0) != (*right < 0) {
+                    Value::Integer(quotient - 1)
+                } else {
+                    Value::Integer(quotient)
+                }
+            }
+            (Value::Integer(_) | Value::Float(_), Value::Integer(_) | Value::Float(_)) => {
+                let as_float = |value: &Value| match value {
+                    Value::Integer(i) => *i as f64,
+                    Value::Float(f) => *f,
+                    _ => unreachable!(),
+                };
+                let (left, right) = (as_float(self), as_float(other));
+                if right == 0.0 {
+                    error!(span, "Division by zero")
+                }
+                let quotient = (left / right).floor();
+                if !quotient.is_finite() || quotient.abs() >= i64::MAX as f64 {
+                    error!(span, "Floor division result doesn't fit in an integer")
+                }
+                Value::Integer(quotient as i64)
+            }
+            _ => error!(span, "Invalid types for floor division"),
+        })
+    }
+
+    /// An integer raised to a non-negative integer stays an integer; a
+    /// negative exponent or any float operand gives a float.
+    pub fn power(&self, other: &Value, span: &Span) -> Result<Value> {
+        Ok(match (self, other) {
+            (Value::Integer(left), Value::Integer(right)) if *right >= 0 => {
+                match checked_pow(*left, *right) {
+                    Some(res) => Value::Integer(res),
+                    None => error!(span, "Integer overflow in exponentiation"),
+                }
+            }
+            (Value::Integer(left), Value::Integer(right)) => {
+                Value::Float((*left as f64).powf(*right as f64))
+            }
+            (Value::Integer(left), Value::Float(right)) => Value::Float((*left as f64).powf(*right)),
+            (Value::Float(left), Value::Float(right)) => Value::Float(left.powf(*right)),
+            (Value::Float(left), Value::Integer(right)) => Value::Float(left.powf(*right as f64)),
+            _ => error!(span, "Invalid types for exponentiation"),
+        })
+    }
+
+    /// Numeric remainder, or printf-style formatting when the left operand
+    /// is a string: `"%s=%d" % ["a", 1]`. A non-array right operand is used
+    /// as the single argument.
+    pub fn modulo(&self, other: &Value, span: &Span) -> Result<Value> {
+        Ok(match (self, other) {
+            (Value::Integer(left), Value::Integer(right)) => {
+                if *right == 0 {
+                    error!(span, "Division by zero")
+                }
+                Value::Integer(left.wrapping_rem(*right))
+            }
+            (Value::Integer(left), Value::Float(right)) => Value::Float(*left as f64 % *right),
+            (Value::Float(left), Value::Float(right)) => Value::Float(*left % *right),
+            (Value::Float(left), Value::Integer(right)) => Value::Float(*left % *right as f64),
+            (Value::String(template), Value::Array(args)) => {
+                Value::String(make!(printf(&template.borrow(), &args.borrow(), span)?))
+            }
+            (Value::String(template), arg) => {
+                Value::String(make!(printf(&template.borrow(), std::slice::from_ref(arg), span)?))
+            }
+            _ => error!(span, "Invalid types for modulo"),
+        })
+    }
+
     pub fn slice(
         &self,
         start: Option<Value>,
@@ -196,51 +337,97 @@ impl Value {
         step: Option<Value>,
         span: &Span,
     ) -> Result<Value> {
-
-        let start = start.unwrap_or_else(|| Value::Integer(0));
-        let step = step.unwrap_or_else(|| Value::Integer(1));
-
-        fn get_slice_params(span: &Span, a: Value, b: Option<Value>, c: Value, default_b: i64) -> Result<(i64, i64, i64)> {
-            match (a, b, c) {
-                (Value::Integer(a), None, Value::Integer(c)) => Ok((a, default_b, c)),
-                (Value::Integer(a), Some(Value::Integer(b)), Value::Integer(c)) => Ok((a, b, c)),
-                _ => error!(span, "Slice indices must be integers"),
+        // Shared by every sliceable type so they all validate the same way.
+        // Missing bounds stay `None`, since their defaults depend on the
+        // direction of the step.
+        type SliceParams = (Option<i64>, Option<i64>, i64);
+        fn get_slice_params(span: &Span, a: Option<Value>, b: Option<Value>, c: Option<Value>) -> Result<SliceParams> {
+            let bound = |v: Option<Value>| match v {
+                None => Ok(None),
+                Some(Value::Integer(i)) => Ok(Some(i)),
+                Some(_) => error!(span, "Slice indices must be integers"),
+            };
+            let (a, b, c) = (bound(a)?, bound(b)?, bound(c)?.unwrap_or(1));
+            if c == 0 {
+                error!(span, "Step cannot be 0");
             }
+            Ok((a, b, c))
         }
 
+        let (start, end, step) = get_slice_params(span, start, end, step)?;
         match self {
             Value::String(s) => {
-                let s = s.borrow();
-                let (start, end, step) = get_slice_params(span, start, end, step, s.len() as i64)?;
-                let res = s.chars().take(end as usize).skip(start as usize).step_by(step as usize).collect::<String>();
+                let chars = s.borrow().chars().collect::<Vec<_>>();
+                let res = slice_items(&chars, start, end, step).into_iter().collect::<String>();
                 Ok(Value::String(make!(res)))
             },
-            Value::Array(a) => {
-                let a = a.borrow();
-                let (start, end, step) = get_slice_params(span, start, end, step, a.len() as i64)?;
-                let res = a.iter().take(end as usize).skip(start as usize).step_by(step as usize).cloned().collect::<Vec<_>>();
-                Ok(Value::Array(make!(res)))
-            },
-            _ => error!(span, "Can only slice strings"),
+            Value::Array(a) => Ok(Value::Array(make!(slice_items(&a.borrow(), start, end, step)))),
+            Value::Bytes(b) => Ok(Value::Bytes(make!(slice_items(&b.borrow(), start, end, step)))),
+            _ => error!(span, "Can only slice strings, arrays and bytes"),
         }
     }
 
-    pub fn not(&self, span: &Span) -> Result<Value> {
+    pub fn negate(&self, span: &Span) -> Result<Value> {
         Ok(match self {
-            Value::Boolean(b) => Value::Boolean(!b),
-            _ => error!(span, "Invalid type for not"),
+            Value::Integer(i) => match i.checked_neg() {
+                Some(res) => Value::Integer(res),
+                None => error!(span, "Integer overflow in negation"),
+            },
+            Value::Float(f) => Value::Float(-f),
+            _ => error!(span, "Invalid type for negation"),
         })
     }
-    pub fn and(&self, other: &Value, span: &Span) -> Result<Value> {
+
+    pub fn bit_not(&self, span: &Span) -> Result<Value> {
+        Ok(match self {
+            Value::Integer(i) => Value::Integer(!i),
+            _ => error!(span, "Bitwise operators only support integers"),
+        })
+    }
+
+    fn bitwise(&self, other: &Value, span: &Span, op: fn(i64, i64) -> i64) -> Result<Value> {
+        Ok(match (self, other) {
+            (Value::Integer(left), Value::Integer(right)) => Value::Integer(op(*left, *right)),
+            _ => error!(span, "Bitwise operators only support integers"),
+        })
+    }
+    pub fn bit_and(&self, other: &Value, span: &Span) -> Result<Value> {
+        self.bitwise(other, span, |a, b| a & b)
+    }
+    pub fn bit_or(&self, other: &Value, span: &Span) -> Result<Value> {
+        self.bitwise(other, span, |a, b| a | b)
+    }
+    pub fn bit_xor(&self, other: &Value, span: &Span) -> Result<Value> {
+        self.bitwise(other, span, |a, b| a ^ b)
+    }
+
+    /// Bits shifted past the top are lost, as with fixed-width integers.
+    pub fn shift_left(&self, other: &Value, span: &Span) -> Result<Value> {
         Ok(match (self, other) {
-            (Value::Boolean(left), Value::Boolean(right)) => Value::Boolean(*left && *right),
-            _ => error!(span, "Invalid types for and"),
+            (Value::Integer(_), Value::Integer(right)) if *right < 0 => {
+                error!(span, "Negative shift amount")
+            }
+            (Value::Integer(left), Value::Integer(right)) => {
+                Value::Integer(if *right >= 64 { 0 } else { left << right })
+            }
+            _ => error!(span, "Bitwise operators only support integers"),
         })
     }
-    pub fn or(&self, other: &Value, span: &Span) -> Result<Value> {
+    /// An arithmetic shift, so negative numbers stay negative.
+    pub fn shift_right(&self, other: &Value, span: &Span) -> Result<Value> {
         Ok(match (self, other) {
-            (Value::Boolean(left), Value::Boolean(right)) => Value::Boolean(*left || *right),
-            _ => error!(span, "Invalid types for or"),
+            (Value::Integer(_), Value::Integer(right)) if *right < 0 => {
+                error!(span, "Negative shift amount")
+            }
+            (Value::Integer(left), Value::Integer(right)) => Value::Integer(left >> right.min(&63)),
+            _ => error!(span, "Bitwise operators only support integers"),
+        })
+    }
+
+    pub fn not(&self, span: &Span) -> Result<Value> {
+        Ok(match self {
+            Value::Boolean(b) => Value::Boolean(!b),
+            _ => error!(span, "Invalid type for not"),
         })
     }
 
@@ -250,6 +437,19 @@ impl Value {
     pub fn not_equals(&self, other: &Value, _: &Span) -> Result<Value> {
         Ok(Value::Boolean(self != other))
     }
+
+    pub fn contains(&self, item: &Value, span: &Span) -> Result<bool> {
+        match self.as_container() {
+            Some(container) => container.contains(item, span),
+            None => error!(span, "Can't check membership in {}", self.type_name()),
+        }
+    }
+    pub fn is_in(&self, container: &Value, span: &Span) -> Result<Value> {
+        Ok(Value::Boolean(container.contains(self, span)?))
+    }
+    pub fn not_in(&self, container: &Value, span: &Span) -> Result<Value> {
+        Ok(Value::Boolean(!container.contains(self, span)?))
+    }
     pub fn less_than(&self, other: &Value, span: &Span) -> Result<Value> {
         Ok(match (self, other) {
             (Value::Integer(left), Value::Integer(right)) => Value::Boolean(*left < *right),
@@ -280,11 +480,57 @@ impl Value {
         other.less_equals(self, span)
     }
 
-    pub fn iterator(&self, span: &Span) -> Result<Value> {
-        Ok(match self {
-            Value::String(s) => Value::Iterator(IteratorValue::for_string(s.clone())),
-            Value::Range(start, end) => Value::Iterator(IteratorValue::for_range(start, end)),
-            Value::Array(arr) => Value::Iterator(IteratorValue::for_array(arr.clone())),
+    /// The name `type()` reports. Scripts compare against these, so they
+    /// must not change.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Boolean(_) => "boolean",
+            Value::String(_) => "string",
+            Value::BuiltInFunction(_) | Value::Function(_) => "function",
+            Value::Iterator(_) => "iterator",
+            Value::Range(..) => "range",
+            Value::Array(_) => "list",
+            Value::Bytes(_) => "bytes",
+            Value::Nothing => "nothing",
+        }
+    }
+
+    /// Conditions in `if`, `while` and `for` use this rule: `false`,
+    /// `nothing`, zero, and empty strings and arrays are falsy, and every
+    /// other value is truthy. `and`, `or`, `not` and `assert` still require
+    /// actual booleans.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Boolean(b) => *b,
+            Value::Integer(i) => *i != 0,
+            Value::Float(f) => *f != 0.0,
+            Value::String(_) | Value::Array(_) | Value::Bytes(_) => self.length() != Some(0),
+            Value::Nothing => false,
+            _ => true,
+        }
+    }
+
+    fn as_container(&self) -> Option<Container<'_>> {
+        Some(match self {
+            Value::String(s) => Container::String(s),
+            Value::Array(arr) => Container::Array(arr),
+            Value::Bytes(bytes) => Container::Bytes(bytes),
+            Value::Range(start, end, step) => Container::Range(*start, *end, *step),
+            _ => return None,
+        })
+    }
+
+    /// Number of items, or `None` if the value isn't a container.
+    pub fn length(&self) -> Option<i64> {
+        self.as_container().map(|container| container.length())
+    }
+
+    pub fn iterator(&self, span: &Span) -> Result<IteratorValue> {
+        Ok(match (self, self.as_container()) {
+            (Value::Iterator(iter), _) => iter.clone(),
+            (_, Some(container)) => container.iterator(),
             _ => error!(span, "Cannot iterate over this type"),
         })
     }
@@ -301,7 +547,8 @@ impl Value {
                 let func = func.borrow();
                 format!("<function {}: {}>", func.name, func.span.0)
             }
-            Value::Range(start, end) => format!("{}..{}", start, end),
+            Value::Range(start, end, 1) => format!("{}..{}", start, end),
+            Value::Range(start, end, step) => format!("range({}, {}, {})", start, end, step),
             Value::BuiltInFunction(name) => format!("<built-in function {}>", name.borrow()),
             Value::Nothing => "nothing".to_string(),
             Value::Array(arr) => {
@@ -316,31 +563,242 @@ impl Value {
                 s.push(']');
                 s
             }
+            Value::Bytes(bytes) => {
+                let mut s = "b\"".to_string();
+                for &b in bytes.borrow().iter() {
+                    match b {
+                        b'"' | b'\\' => s.extend(['\\', b as char]),
+                        b' '..=b'~' => s.push(b as char),
+                        _ => s.push_str(&format!("\\x{:02x}", b)),
+                    }
+                }
+                s.push('"');
+                s
+            }
         }
     }
 
     pub fn create_range(start: &Value, end: &Value, span: &Span) -> Result<Value> {
         Ok(match (start, end) {
-            (Value::Integer(start), Value::Integer(end)) => Value::Range(*start, *end),
+            (Value::Integer(start), Value::Integer(end)) => Value::Range(*start, *end, 1),
             _ => error!(span, "Must be integers for range"),
         })
     }
 
     pub fn index(&self, index: &Value, span: &Span) -> Result<Value> {
-        Ok(match (self, index) {
-            (Value::String(s), Value::Integer(index)) => {
-                match s.borrow().chars().nth(*index as usize) {
-                    Some(c) => Value::String(make!(c.to_string())),
+        Ok(match (self.as_container(), index) {
+            (Some(container), Value::Integer(index)) => match container.get(*index) {
+                Some(item) => item,
+                None => error!(span, "Index out of bounds"),
+            },
+            _ => error!(span, "Can't index {:?} with {:?}", self, index),
+        })
+    }
+
+    pub fn member(&self, name: &str, span: &Span) -> Result<Value> {
+        Ok(match (self, name) {
+            (Value::Range(start, _, _), "start") => Value::Integer(*start),
+            (Value::Range(_, end, _), "end") => Value::Integer(*end),
+            (Value::Range(_, _, step), "step") => Value::Integer(*step),
+            _ => error!(span, "{} has no member `{}`", self.type_name(), name),
+        })
+    }
+
+    pub fn set_index(&self, index: &Value, value: Value, span: &Span) -> Result<()> {
+        match (self, index) {
+            (Value::Array(arr), Value::Integer(index)) => {
+                match arr.borrow_mut().get_mut(*index as usize) {
+                    Some(item) => *item = value,
                     None => error!(span, "Index out of bounds"),
                 }
             }
-            (Value::Array(arr), Value::Integer(index)) => {
-                match arr.borrow().get(*index as usize) {
-                    Some(v) => v.clone(),
+            (Value::Bytes(bytes), Value::Integer(index)) => {
+                let byte = match value {
+                    Value::Integer(b @ 0..=255) => b as u8,
+                    _ => error!(span, "Bytes can only hold integers between 0 and 255"),
+                };
+                match bytes.borrow_mut().get_mut(*index as usize) {
+                    Some(item) => *item = byte,
                     None => error!(span, "Index out of bounds"),
                 }
             }
-            (value, index) => error!(span, "Can't index {:?} with {:?}", value, index),
+            (value, index) => error!(span, "Can't assign to index {:?} of {:?}", index, value),
+        }
+        Ok(())
+    }
+}
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{}", s.borrow()),
+            _ => write!(f, "{}", self.repr()),
+        }
+    }
+}
+
+/// The container types. Each implements `length`, `get`, `iterator` and
+/// `contains` here once, and everything else (`len`, indexing, truthiness,
+/// `for` loops, `in`, builtins taking iterables) goes through them, so a
+/// new container type only needs an arm in each.
+enum Container<'a> {
+    String(&'a Ref<String>),
+    Array(&'a Ref<Vec<Value>>),
+    Bytes(&'a Ref<Vec<u8>>),
+    Range(i64, i64, i64),
+}
+
+impl Container<'_> {
+    /// Strings count characters, matching indexing and slicing.
+    fn length(&self) -> i64 {
+        match self {
+            Container::String(s) => s.borrow().chars().count() as i64,
+            Container::Array(arr) => arr.borrow().len() as i64,
+            Container::Bytes(bytes) => bytes.borrow().len() as i64,
+            Container::Range(start, end, step) => range_len(*start, *end, *step),
+        }
+    }
+
+    /// The item at `index`, or `None` if it's out of bounds.
+    fn get(&self, index: i64) -> Option<Value> {
+        let index = usize::try_from(index).ok()?;
+        match self {
+            Container::String(s) => {
+                let c = s.borrow().chars().nth(index)?;
+                Some(Value::String(make!(c.to_string())))
+            }
+            Container::Array(arr) => arr.borrow().get(index).cloned(),
+            Container::Bytes(bytes) => bytes.borrow().get(index).map(|b| Value::Integer(*b as i64)),
+            Container::Range(start, end, step) => {
+                if index as i128 >= range_len(*start, *end, *step) as i128 {
+                    return None;
+                }
+                // In-bounds items lie between `start` and `end`, but the
+                // intermediate product can still overflow an i64.
+                let item = *start as i128 + index as i128 * *step as i128;
+                Some(Value::Integer(item as i64))
+            }
+        }
+    }
+
+    fn iterator(&self) -> IteratorValue {
+        match self {
+            Container::String(s) => IteratorValue::for_string((*s).clone()),
+            Container::Array(arr) => IteratorValue::for_array((*arr).clone()),
+            Container::Bytes(bytes) => IteratorValue::for_bytes((*bytes).clone()),
+            Container::Range(start, end, step) => IteratorValue::for_range(*start, *end, *step),
+        }
+    }
+
+    /// Substrings for strings, and otherwise whether iterating would
+    /// produce `item`.
+    fn contains(&self, item: &Value, span: &Span) -> Result<bool> {
+        Ok(match (self, item) {
+            (Container::String(s), Value::String(sub)) => {
+                s.borrow().contains(sub.borrow().as_str())
+            }
+            (Container::String(_), _) => error!(span, "Can only search for strings in a string"),
+            (Container::Array(arr), _) => arr.borrow().contains(item),
+            (Container::Bytes(bytes), Value::Integer(i)) => {
+                u8::try_from(*i).is_ok_and(|byte| bytes.borrow().contains(&byte))
+            }
+            (Container::Bytes(_), _) => false,
+            (Container::Range(start, end, step), Value::Integer(i)) => {
+                let in_bounds = if *step > 0 { start <= i && i < end } else { end < i && i <= start };
+                in_bounds && (*i as i128 - *start as i128) % *step as i128 == 0
+            }
+            (Container::Range(..), _) => false,
         })
     }
-}
\ No newline at end of file
+}
+
+/// The integers in a range, stopping early rather than overflowing.
+pub fn range_items(start: i64, end: i64, step: i64) -> impl Iterator<Item = i64> {
+    std::iter::successors(Some(start), move |i| i.checked_add(step))
+        .take_while(move |i| if step > 0 { *i < end } else { *i > end })
+}
+
+pub fn range_len(start: i64, end: i64, step: i64) -> i64 {
+    // Widened so that ranges spanning most of i64 don't overflow
+    let (start, end, step) = (start as i128, end as i128, step as i128);
+    let len = if step > 0 {
+        (end - start + step - 1) / step
+    } else {
+        (start - end - step - 1) / -step
+    };
+    len.clamp(0, i64::MAX as i128) as i64
+}
+
+// Like i64::checked_pow, but for exponents that don't fit in a u32
+fn checked_pow(mut base: i64, mut exp: i64) -> Option<i64> {
+    let mut acc: i64 = 1;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            acc = acc.checked_mul(base)?;
+        }
+        exp >>= 1;
+        if exp > 0 {
+            base = base.checked_mul(base)?;
+        }
+    }
+    Some(acc)
+}
+
+// Negative bounds count from the end. With a negative step the slice walks
+// backwards, starting from the last item by default and running past the
+// first one.
+fn slice_items<T: Clone>(items: &[T], start: Option<i64>, end: Option<i64>, step: i64) -> Vec<T> {
+    let len = items.len() as i64;
+    let normalize = |i: i64| if i < 0 { i.saturating_add(len) } else { i };
+    let (start, end) = (start.map(normalize), end.map(normalize));
+    if step > 0 {
+        let start = start.unwrap_or(0).clamp(0, len);
+        let end = end.unwrap_or(len).clamp(start, len);
+        items[start as usize..end as usize].iter().step_by(step as usize).cloned().collect()
+    } else {
+        let start = start.unwrap_or(len - 1).clamp(-1, len - 1);
+        let end = end.unwrap_or(-1).clamp(-1, len - 1);
+        let mut res = vec![];
+        let mut i = start;
+        while i > end {
+            res.push(items[i as usize].clone());
+            i += step;
+        }
+        res
+    }
+}
+
+fn printf(template: &str, args: &[Value], span: &Span) -> Result<String> {
+    let mut res = String::new();
+    let mut args = args.iter();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            res.push(c);
+            continue;
+        }
+        let spec = match chars.next() {
+            Some('%') => {
+                res.push('%');
+                continue;
+            }
+            Some(spec) => spec,
+            None => error!(span, "Incomplete format specifier at end of string"),
+        };
+        let arg = match args.next() {
+            Some(arg) => arg,
+            None => error!(span, "Not enough arguments for format string"),
+        };
+        match (spec, arg) {
+            ('s', arg) => res.push_str(&arg.to_string()),
+            ('d', Value::Integer(i)) => res.push_str(&i.to_string()),
+            ('f', Value::Integer(i)) => res.push_str(&format!("{:.6}", *i as f64)),
+            ('f', Value::Float(f)) => res.push_str(&format!("{:.6}", f)),
+            ('d' | 'f', arg) => error!(span, "%{} expects a number, got {:?}", spec, arg),
+            (spec, _) => error!(span, "Unknown format specifier %{}", spec),
+        }
+    }
+    if args.next().is_some() {
+        error!(span, "Too many arguments for format string");
+    }
+    Ok(res)
+}
@@ -1,5 +1,5 @@
 use crate::error::{lexer_error as error, Result};
-use crate::common::{Location, Span};
+use crate::common::{Location, Span, DEFAULT_TAB_WIDTH};
 use crate::token::{Token, TokenKind};
 
 #[derive(Debug)]
@@ -8,6 +8,10 @@ pub struct Lexer {
     input: String,
     current_index: usize,
     seen_newline: bool,
+    tab_width: usize,
+    /// One entry per f-string `{...}` being lexed, counting the braces
+    /// opened inside it so we know which `}` closes the interpolation.
+    interpolations: Vec<usize>,
 }
 
 impl Lexer {
@@ -16,14 +20,22 @@ impl Lexer {
             location: Location {
                 line: 1,
                 column: 1,
+                char_column: 1,
                 filename,
             },
             input,
             current_index: 0,
             seen_newline: false,
+            tab_width: DEFAULT_TAB_WIDTH,
+            interpolations: vec![],
         }
     }
 
+    /// How many columns a tab advances to; 1 counts a tab as one column.
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width.max(1);
+    }
+
     fn cur(&self) -> Option<char> {
         self.input.chars().nth(self.current_index)
     }
@@ -37,12 +49,20 @@ impl Lexer {
             Some('\n') => {
                 self.location.line += 1;
                 self.location.column = 1;
+                self.location.char_column = 1;
                 self.current_index += 1;
                 self.seen_newline = true;
             }
+            Some('\t') => {
+                let column = self.location.column - 1;
+                self.location.column = (column / self.tab_width + 1) * self.tab_width + 1;
+                self.location.char_column += 1;
+                self.current_index += 1;
+            }
             Some(_) => {
                 self.current_index += 1;
                 self.location.column += 1;
+                self.location.char_column += 1;
             }
             None => {}
         }
@@ -54,7 +74,8 @@ impl Lexer {
 
     fn push_simple(&mut self, tokens: &mut Vec<Token>, kind: TokenKind, len: usize) {
         let start = self.loc();
-        let text = self.input[self.current_index..self.current_index + len].to_string();
+        // `current_index` counts characters, not bytes
+        let text = self.input.chars().skip(self.current_index).take(len).collect();
         for _ in 0..len {
             self.increment();
         }
@@ -67,6 +88,44 @@ impl Lexer {
         self.seen_newline = false;
     }
 
+    // Consumes the newline too, so the next token still sees `newline_before`
+    fn skip_line_comment(&mut self) {
+        while let Some(c) = self.cur() {
+            self.increment();
+            if c == '\n' {
+                break;
+            }
+        }
+    }
+
+    /// Block comments nest, so code containing them can be commented out.
+    fn skip_block_comment(&mut self) -> Result<()> {
+        let start = self.loc();
+        self.increment();
+        self.increment();
+        let opening = Span(start, self.loc());
+        let mut depth = 1;
+        loop {
+            match (self.cur(), self.peek(1)) {
+                (Some('/'), Some('*')) => {
+                    depth += 1;
+                    self.increment();
+                }
+                (Some('*'), Some('/')) => {
+                    depth -= 1;
+                    self.increment();
+                    if depth == 0 {
+                        self.increment();
+                        return Ok(());
+                    }
+                }
+                (None, _) => error!(opening, "Unterminated block comment"),
+                _ => {}
+            }
+            self.increment();
+        }
+    }
+
     pub fn lex(&mut self) -> Result<Vec<Token>> {
         let mut tokens: Vec<Token> = vec![];
         while let Some(c) = self.cur() {
@@ -75,7 +134,7 @@ impl Lexer {
                 c if c.is_whitespace() => self.increment(),
 
                 // base N literals, i.e. 0b_1101, 0o_567, 0x_ff
-                '0' if self.peek(1).map_or(false, |c| "box".contains(c)) => {
+                '0' if self.peek(1).is_some_and(|c| "box".contains(c)) => {
                     let mut num = String::new();
 
                     let base = match self.peek(1) {
@@ -95,62 +154,82 @@ impl Lexer {
                     );
                 }
 
-                // decimal int/float literals
+                // decimal int/float literals, i.e. 1_000, 1.5, 6.022e23
                 '0'..='9' => {
                     let mut num = String::new();
+                    let mut kind = TokenKind::IntegerLiteralDec;
 
                     self.lex_num(&mut num, Base::Dec, &start)?;
-                    if let Some('.') = self.cur() {
-                        if let Some('.') = self.peek(1) {
-                            self.push(
-                                &mut tokens,
-                                Token::new(
-                                    TokenKind::IntegerLiteralDec,
-                                    Span(start, self.loc()),
-                                    num,
-                                ),
-                            );
-                        } else {
-                            num.push('.');
+                    // `1..2` is a range, not a float
+                    if self.cur() == Some('.') && self.peek(1) != Some('.') {
+                        kind = TokenKind::FloatLiteral;
+                        num.push('.');
+                        self.increment();
+                        if self.cur() == Some('_') {
+                            error!(Span(start, self.loc()), "Invalid numerical literal");
+                        }
+                        self.lex_num(&mut num, Base::Dec, &start)?;
+                    }
+                    if let Some('e' | 'E') = self.cur() {
+                        kind = TokenKind::FloatLiteral;
+                        num.push('e');
+                        self.increment();
+                        if let Some(sign @ ('+' | '-')) = self.cur() {
+                            num.push(sign);
                             self.increment();
-                            self.lex_num(&mut num, Base::Dec, &start)?;
-                            self.push(
-                                &mut tokens,
-                                Token::new(TokenKind::FloatLiteral, Span(start, self.loc()), num),
-                            );
                         }
-                    } else {
-                        self.push(
-                            &mut tokens,
-                            Token::new(TokenKind::IntegerLiteralDec, Span(start, self.loc()), num),
-                        );
+                        if !self.cur().is_some_and(|c| c.is_ascii_digit()) {
+                            error!(Span(start, self.loc()), "Missing exponent in numerical literal");
+                        }
+                        self.lex_num(&mut num, Base::Dec, &start)?;
                     }
+                    self.push(&mut tokens, Token::new(kind, Span(start, self.loc()), num));
                 }
                 '+' => match self.peek(1) {
                     Some('+') => self.push_simple(&mut tokens, TokenKind::PlusPlus, 2),
+                    Some('=') => self.push_simple(&mut tokens, TokenKind::PlusEquals, 2),
                     _ => self.push_simple(&mut tokens, TokenKind::Plus, 1),
                 },
                 '-' => match self.peek(1) {
                     Some('-') => self.push_simple(&mut tokens, TokenKind::MinusMinus, 2),
+                    Some('=') => self.push_simple(&mut tokens, TokenKind::MinusEquals, 2),
                     _ => self.push_simple(&mut tokens, TokenKind::Minus, 1),
                 },
-                '*' => self.push_simple(&mut tokens, TokenKind::Star, 1),
+                '*' => match self.peek(1) {
+                    Some('*') => self.push_simple(&mut tokens, TokenKind::StarStar, 2),
+                    Some('=') => self.push_simple(&mut tokens, TokenKind::StarEquals, 2),
+                    _ => self.push_simple(&mut tokens, TokenKind::Star, 1),
+                },
+                '%' => match self.peek(1) {
+                    Some('=') => self.push_simple(&mut tokens, TokenKind::PercentEquals, 2),
+                    _ => self.push_simple(&mut tokens, TokenKind::Percent, 1),
+                },
                 '/' => match self.peek(1) {
-                    Some('/') => {
-                        while let Some(c) = self.cur() {
-                            self.increment();
-                            if c == '\n' {
-                                break;
-                            }
-                        }
+                    // `//` is only ever floor division, which can't start a
+                    // line, so point old `//` comments at `#` instead.
+                    Some('/') if self.seen_newline || tokens.is_empty() => {
+                        let start = self.loc();
+                        self.increment();
+                        self.increment();
+                        error!(
+                            Span(start, self.loc()),
+                            "`//` is floor division, not a comment; use `#` for comments"
+                        );
                     }
+                    Some('/') => self.push_simple(&mut tokens, TokenKind::SlashSlash, 2),
+                    Some('*') => self.skip_block_comment()?,
+                    Some('=') => self.push_simple(&mut tokens, TokenKind::SlashEquals, 2),
                     _ => self.push_simple(&mut tokens, TokenKind::Slash, 1),
                 },
+                '#' => self.skip_line_comment(),
                 '(' => self.push_simple(&mut tokens, TokenKind::LeftParen, 1),
                 ')' => self.push_simple(&mut tokens, TokenKind::RightParen, 1),
                 '[' => self.push_simple(&mut tokens, TokenKind::LeftBracket, 1),
                 ']' => self.push_simple(&mut tokens, TokenKind::RightBracket, 1),
                 '|' => self.push_simple(&mut tokens, TokenKind::Pipe, 1),
+                '&' => self.push_simple(&mut tokens, TokenKind::Ampersand, 1),
+                '^' => self.push_simple(&mut tokens, TokenKind::Caret, 1),
+                '~' => self.push_simple(&mut tokens, TokenKind::Tilde, 1),
                 ':' => self.push_simple(&mut tokens, TokenKind::Colon, 1),
                 '=' => match self.peek(1) {
                     Some('>') => self.push_simple(&mut tokens, TokenKind::FatArrow, 2),
@@ -158,10 +237,12 @@ impl Lexer {
                     _ => self.push_simple(&mut tokens, TokenKind::Equals, 1),
                 },
                 '<' => match self.peek(1) {
+                    Some('<') => self.push_simple(&mut tokens, TokenKind::LessLess, 2),
                     Some('=') => self.push_simple(&mut tokens, TokenKind::LessEquals, 2),
                     _ => self.push_simple(&mut tokens, TokenKind::LessThan, 1),
                 },
                 '>' => match self.peek(1) {
+                    Some('>') => self.push_simple(&mut tokens, TokenKind::GreaterGreater, 2),
                     Some('=') => self.push_simple(&mut tokens, TokenKind::GreaterEquals, 2),
                     _ => self.push_simple(&mut tokens, TokenKind::GreaterThan, 1),
                 },
@@ -171,8 +252,24 @@ impl Lexer {
                 },
                 ';' => self.push_simple(&mut tokens, TokenKind::SemiColon, 1),
                 ',' => self.push_simple(&mut tokens, TokenKind::Comma, 1),
-                '{' => self.push_simple(&mut tokens, TokenKind::LeftBrace, 1),
-                '}' => self.push_simple(&mut tokens, TokenKind::RightBrace, 1),
+                '{' => {
+                    if let Some(depth) = self.interpolations.last_mut() {
+                        *depth += 1;
+                    }
+                    self.push_simple(&mut tokens, TokenKind::LeftBrace, 1)
+                }
+                '}' => match self.interpolations.last_mut() {
+                    Some(0) => {
+                        self.interpolations.pop();
+                        self.push_simple(&mut tokens, TokenKind::RightBrace, 1);
+                        self.lex_format_string_part(&mut tokens)?;
+                    }
+                    Some(depth) => {
+                        *depth -= 1;
+                        self.push_simple(&mut tokens, TokenKind::RightBrace, 1)
+                    }
+                    None => self.push_simple(&mut tokens, TokenKind::RightBrace, 1),
+                },
                 '@' => self.push_simple(&mut tokens, TokenKind::At, 1),
                 '"' => {
                     let token = self.lex_string_literal()?;
@@ -183,6 +280,11 @@ impl Lexer {
                     _ => self.push_simple(&mut tokens, TokenKind::Dot, 1),
                 },
 
+                'f' if self.peek(1) == Some('"') => {
+                    self.push_simple(&mut tokens, TokenKind::FormatStringStart, 2);
+                    self.lex_format_string_part(&mut tokens)?;
+                }
+
                 // identifiers
                 'a'..='z' | 'A'..='Z' | '_' => {
                     let mut ident = String::new();
@@ -219,6 +321,10 @@ impl Lexer {
                     ));
                 }
                 '\n' => break,
+                '\\' => match self.lex_escape()? {
+                    Some(c) => string.push(c),
+                    None => break,
+                },
                 _ => {
                     string.push(c);
                     self.increment();
@@ -228,7 +334,80 @@ impl Lexer {
         error!(Span(start, self.loc()), "Unterminated string literal");
     }
 
+    /// Lexes the literal text of an f-string up to the next interpolation or
+    /// the closing quote. An interpolation pushes its `{` and hands control
+    /// back to `lex`, which comes back here once the matching `}` is seen.
+    fn lex_format_string_part(&mut self, tokens: &mut Vec<Token>) -> Result<()> {
+        let start = self.loc();
+        let mut string = String::new();
+        while let Some(c) = self.cur() {
+            match c {
+                '"' => {
+                    let span = Span(start, self.loc());
+                    self.push(tokens, Token::new(TokenKind::StringLiteral, span, string));
+                    self.push_simple(tokens, TokenKind::FormatStringEnd, 1);
+                    return Ok(());
+                }
+                '{' | '}' if self.peek(1) == Some(c) => {
+                    string.push(c);
+                    self.increment();
+                    self.increment();
+                }
+                '{' => {
+                    let span = Span(start, self.loc());
+                    self.push(tokens, Token::new(TokenKind::StringLiteral, span, string));
+                    self.push_simple(tokens, TokenKind::LeftBrace, 1);
+                    self.interpolations.push(0);
+                    return Ok(());
+                }
+                '}' => {
+                    let loc = self.loc();
+                    self.increment();
+                    error!(
+                        Span(loc, self.loc()),
+                        "Single `}}` in f-string, use `}}}}` for a literal brace"
+                    )
+                }
+                '\n' => break,
+                '\\' => match self.lex_escape()? {
+                    Some(c) => string.push(c),
+                    None => break,
+                },
+                _ => {
+                    string.push(c);
+                    self.increment();
+                }
+            }
+        }
+        error!(Span(start, self.loc()), "Unterminated f-string");
+    }
+
+    /// Consumes a backslash escape, returning `None` if the string ends
+    /// right after the backslash.
+    fn lex_escape(&mut self) -> Result<Option<char>> {
+        let start = self.loc();
+        self.increment();
+        let escaped = match self.cur() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some(c) if c != '\n' => {
+                self.increment();
+                error!(Span(start, self.loc()), "Unknown escape sequence `\\{}`", c)
+            }
+            _ => return Ok(None),
+        };
+        self.increment();
+        Ok(Some(escaped))
+    }
+
+    /// Underscores may separate digits (or follow a base prefix), but can't
+    /// be doubled or end the number.
     fn lex_num(&mut self, num: &mut String, base: Base, start: &Location) -> Result<()> {
+        let mut after_underscore = false;
         while let Some(mut c) = self.cur() {
             c = c.to_ascii_lowercase();
             match (base, c) {
@@ -238,14 +417,26 @@ impl Lexer {
                 | (Base::Hex, '0'..='9' | 'a'..='f') => {
                     num.push(c);
                     self.increment();
+                    after_underscore = false;
                 }
+                // The exponent of a decimal literal
+                (Base::Dec, 'e') => break,
                 (_, '0'..='9' | 'a'..='f') => {
                     error!(Span(*start, self.loc()), "Invalid numerical literal");
                 }
-                (_, '_') => self.increment(),
+                (_, '_') if after_underscore => {
+                    error!(Span(*start, self.loc()), "Consecutive underscores in numerical literal");
+                }
+                (_, '_') => {
+                    self.increment();
+                    after_underscore = true;
+                }
                 _ => break,
             }
         }
+        if after_underscore {
+            error!(Span(*start, self.loc()), "Numerical literal can't end with an underscore");
+        }
         Ok(())
     }
 }
@@ -268,3 +459,112 @@ impl From<Base> for TokenKind {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Lexer;
+    use crate::error::Result;
+    use crate::token::TokenKind;
+
+    fn kinds(source: &str) -> Result<Vec<TokenKind>> {
+        let tokens = Lexer::new(source.to_string(), "<test>").lex()?;
+        Ok(tokens.into_iter().map(|token| token.kind).collect())
+    }
+
+    #[test]
+    fn slash_slash_is_never_a_comment() {
+        use TokenKind::*;
+        let hint = "`//` is floor division, not a comment; use `#` for comments";
+        assert_eq!(error("// nothing here"), hint);
+        assert_eq!(error("1\n  // 2 3\n4"), hint);
+        assert_eq!(
+            kinds("1\n  # 2 3\n4").unwrap(),
+            vec![IntegerLiteralDec, IntegerLiteralDec, EOF]
+        );
+        assert_eq!(
+            kinds("7 // 2").unwrap(),
+            vec![IntegerLiteralDec, SlashSlash, IntegerLiteralDec, EOF]
+        );
+    }
+    #[test]
+    fn negative_based_literals_lex_as_minus_and_magnitude() {
+        use TokenKind::*;
+        assert_eq!(kinds("-0xFF").unwrap(), vec![Minus, IntegerLiteralHex, EOF]);
+        assert_eq!(kinds("-0b10").unwrap(), vec![Minus, IntegerLiteralBin, EOF]);
+        assert_eq!(kinds("-0o17").unwrap(), vec![Minus, IntegerLiteralOct, EOF]);
+    }
+    #[test]
+    fn tabs_advance_to_the_next_tab_stop() {
+        let column = |source: &str, tab_width: usize| {
+            let mut lexer = Lexer::new(source.to_string(), "<test>");
+            lexer.set_tab_width(tab_width);
+            let token = &lexer.lex().unwrap()[0];
+            (token.span.0.column, token.span.0.char_column)
+        };
+        assert_eq!(column("\tx", 8), (9, 2));
+        assert_eq!(column("\tx", 4), (5, 2));
+        assert_eq!(column("  \tx", 8), (9, 4));
+        assert_eq!(column("\t\tx", 1), (3, 3));
+    }
+
+    #[test]
+    fn hash_comments_are_skipped() {
+        assert_eq!(kinds("1 + 2 # three\n4").unwrap(), kinds("1 + 2\n4").unwrap());
+        assert_eq!(kinds("# only a comment").unwrap(), vec![TokenKind::EOF]);
+        let tokens = Lexer::new("1 # x\n2".to_string(), "<test>").lex().unwrap();
+        assert!(tokens[1].newline_before);
+    }
+
+    fn string(source: &str) -> Result<String> {
+        let tokens = Lexer::new(source.to_string(), "<test>").lex()?;
+        Ok(tokens[0].text.clone())
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(string(r#""a\nb""#).unwrap(), "a\nb");
+        assert_eq!(string(r#""a\tb""#).unwrap(), "a\tb");
+        assert_eq!(string(r#""a\rb""#).unwrap(), "a\rb");
+        assert_eq!(string(r#""a\0b""#).unwrap(), "a\0b");
+        assert_eq!(string(r#""a\\b""#).unwrap(), "a\\b");
+        assert_eq!(string(r#""a\"b""#).unwrap(), "a\"b");
+        assert_eq!(string(r#""a\qb""#).unwrap_err().message, "Unknown escape sequence `\\q`");
+    }
+
+    fn error(source: &str) -> String {
+        match Lexer::new(source.to_string(), "<test>").lex() {
+            Ok(tokens) => panic!("expected an error, got {:?}", tokens),
+            Err(err) => err.message,
+        }
+    }
+
+    #[test]
+    fn numeric_literals_with_exponents_and_underscores() {
+        use TokenKind::*;
+        assert_eq!(kinds("1_000").unwrap(), vec![IntegerLiteralDec, EOF]);
+        assert_eq!(kinds("1.5e3").unwrap(), vec![FloatLiteral, EOF]);
+        assert_eq!(kinds("2e-2").unwrap(), vec![FloatLiteral, EOF]);
+        assert_eq!(kinds("0x_ff").unwrap(), vec![IntegerLiteralHex, EOF]);
+    }
+
+    #[test]
+    fn malformed_numeric_literals() {
+        assert_eq!(error("1__000"), "Consecutive underscores in numerical literal");
+        assert_eq!(error("1000_"), "Numerical literal can't end with an underscore");
+        assert_eq!(error("1.5_"), "Numerical literal can't end with an underscore");
+        assert_eq!(error("1e"), "Missing exponent in numerical literal");
+        assert_eq!(error("1.5e+"), "Missing exponent in numerical literal");
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("1 /* a /* b */ c */ 2").unwrap(),
+            vec![IntegerLiteralDec, IntegerLiteralDec, EOF]
+        );
+        assert_eq!(kinds("/**/1").unwrap(), vec![IntegerLiteralDec, EOF]);
+        assert_eq!(error("1 /* a /* b */"), "Unterminated block comment");
+        assert_eq!(error("/* a"), "Unterminated block comment");
+    }
+}
@@ -1,8 +1,11 @@
 #![allow(clippy::upper_case_acronyms)]
 // Clippy is being annoying with this one, so we'll just ignore it.
 #![allow(clippy::let_and_return)]
+// Errors carry a span and a trace, and are only built on the failure path.
+#![allow(clippy::result_large_err)]
 
-use crate::error::Result;
+use crate::common::{Location, Span, DEFAULT_TAB_WIDTH};
+use crate::error::{Error, ErrorKind, Result};
 
 mod ast;
 mod error;
@@ -13,33 +16,248 @@ mod common;
 mod repl;
 mod token;
 
-fn run_file(filename: &str) -> Result<()> {
-    let content = std::fs::read_to_string(filename).expect("Couldn't open input file");
+fn read_source(filename: &'static str) -> Result<String> {
+    std::fs::read_to_string(filename).map_err(|err| {
+        let loc = Location { line: 1, column: 1, char_column: 1, filename };
+        Error {
+            kind: ErrorKind::IO,
+            span: Span(loc, loc),
+            message: format!("Couldn't read {}: {}", filename, err),
+            trace: vec![],
+        }
+    })
+}
+
+fn report(err: &Error, tab_width: usize) {
+    match err.kind {
+        ErrorKind::Abort => err.print_abort(),
+        ErrorKind::IO => eprintln!("{}", err),
+        _ => err.print_with_source(tab_width),
+    }
+}
+
+fn run_file(filename: &str, tab_width: usize, max_call_depth: usize) -> Result<()> {
+    let filename: &'static str = Box::leak(filename.to_string().into_boxed_str());
+    let content = read_source(filename)?;
 
-    let mut lex = lexer::Lexer::new(content, Box::leak(filename.to_string().into_boxed_str()));
+    let mut lex = lexer::Lexer::new(content, filename);
+    lex.set_tab_width(tab_width);
     let tokens = lex.lex()?;
 
     let mut parser = parser::Parser::new(tokens);
     let ast = parser.parse()?;
 
     let mut interpreter = interpreter::Interpreter::new();
+    interpreter.set_max_call_depth(max_call_depth);
     interpreter.execute(&ast)?;
     Ok(())
 }
 
-fn main() {
-    let args = std::env::args().collect::<Vec<String>>();
-    if args.len() == 1 {
-        let mut repl = repl::Repl::new();
-        repl.run();
-        std::process::exit(0);
+fn check_file(filename: &str, tab_width: usize) -> bool {
+    let filename: &'static str = Box::leak(filename.to_string().into_boxed_str());
+    let tokens = read_source(filename).and_then(|content| {
+        let mut lex = lexer::Lexer::new(content, filename);
+        lex.set_tab_width(tab_width);
+        lex.lex()
+    });
+    let tokens = match tokens {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            report(&err, tab_width);
+            return false;
+        }
+    };
+
+    let mut parser = parser::Parser::new(tokens);
+    match parser.parse_with_recovery() {
+        Ok(_) => true,
+        Err(errors) => {
+            for err in errors {
+                err.print_with_source(tab_width);
+            }
+            false
+        }
+    }
+}
+
+fn usage() -> String {
+    format!(
+        "\
+Usage:
+    serpens                 start the REPL
+    serpens <file>          run a file
+    serpens run <file>      run a file
+    serpens check <file>    report all syntax errors in a file
+    serpens repl            start the REPL
+
+Options:
+    --tab-width <n>         columns a tab advances to in error locations
+                            (default 8, use 1 to count a tab as one column)
+    --max-recursion <n>     how many nested function calls are allowed
+                            (default {}, at most {})",
+        interpreter::DEFAULT_MAX_CALL_DEPTH,
+        interpreter::MAX_CALL_DEPTH
+    )
+}
+
+enum Command {
+    Repl,
+    Run(String),
+    Check(String),
+}
+
+struct Args {
+    command: Command,
+    tab_width: usize,
+    max_call_depth: usize,
+}
+
+fn parse_args(mut args: Vec<String>) -> std::result::Result<Args, String> {
+    let mut tab_width = DEFAULT_TAB_WIDTH;
+    if let Some(i) = args.iter().position(|arg| arg == "--tab-width") {
+        tab_width = match args.get(i + 1).map(|width| width.parse()) {
+            Some(Ok(width)) if width > 0 => width,
+            _ => return Err("`--tab-width` expects a positive number".to_string()),
+        };
+        args.drain(i..i + 2);
+    }
+    let mut max_call_depth = interpreter::DEFAULT_MAX_CALL_DEPTH;
+    if let Some(i) = args.iter().position(|arg| arg == "--max-recursion") {
+        max_call_depth = match args.get(i + 1).map(|depth| depth.parse()) {
+            Some(Ok(depth)) if depth > 0 => depth,
+            _ => return Err("`--max-recursion` expects a positive number".to_string()),
+        };
+        if max_call_depth > interpreter::MAX_CALL_DEPTH {
+            return Err(format!(
+                "`--max-recursion` can be at most {}",
+                interpreter::MAX_CALL_DEPTH
+            ));
+        }
+        args.drain(i..i + 2);
     }
 
-    match run_file(&args[1]) {
-        Ok(_) => std::process::exit(0),
+    let command = match args.as_slice() {
+        [] => Ok(Command::Repl),
+        [cmd] if cmd == "repl" => Ok(Command::Repl),
+        [cmd, file] if cmd == "run" => Ok(Command::Run(file.clone())),
+        [cmd, file] if cmd == "check" => Ok(Command::Check(file.clone())),
+        [cmd, ..] if matches!(cmd.as_str(), "run" | "check" | "repl") => {
+            Err(format!("Invalid arguments for `{}`", cmd))
+        }
+        [file] if !file.starts_with('-') => Ok(Command::Run(file.clone())),
+        _ => Err("Invalid arguments".to_string()),
+    }?;
+    Ok(Args {
+        command,
+        tab_width,
+        max_call_depth,
+    })
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let args = match parse_args(args) {
+        Ok(args) => args,
+        Err(msg) => {
+            eprintln!("{}\n\n{}", msg, usage());
+            std::process::exit(2);
+        }
+    };
+
+    // The interpreter recurses natively for every nested call, so it runs on
+    // a thread with enough stack to reach the recursion limit. Only the pages
+    // actually used are committed.
+    let stack_size = interpreter::stack_size(args.max_call_depth);
+    let child = match std::thread::Builder::new()
+        .stack_size(stack_size)
+        .spawn(move || run_main(args))
+    {
+        Ok(child) => child,
         Err(err) => {
-            err.print_with_source();
-            std::process::exit(1);
+            eprintln!("Couldn't reserve {} bytes of stack: {}", stack_size, err);
+            std::process::exit(2);
+        }
+    };
+    // The command exits the process itself; this only returns on a panic
+    if child.join().is_err() {
+        std::process::exit(101);
+    }
+}
+
+fn run_main(args: Args) {
+    let Args {
+        command,
+        tab_width,
+        max_call_depth,
+    } = args;
+
+    match command {
+        Command::Repl => {
+            let mut repl = repl::Repl::new();
+            repl.set_max_call_depth(max_call_depth);
+            repl.run();
+            std::process::exit(0);
+        }
+        Command::Check(filename) => {
+            std::process::exit(if check_file(&filename, tab_width) { 0 } else { 1 });
         }
+        Command::Run(filename) => match run_file(&filename, tab_width, max_call_depth) {
+            Ok(_) => std::process::exit(0),
+            Err(err) => {
+                report(&err, tab_width);
+                std::process::exit(1);
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{interpreter, parse_args, usage, Args, Command};
+
+    fn parse(args: &[&str]) -> std::result::Result<Args, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()).collect())
+    }
+
+    #[test]
+    fn subcommands_dispatch() {
+        assert!(matches!(parse(&[]).unwrap().command, Command::Repl));
+        assert!(matches!(parse(&["repl"]).unwrap().command, Command::Repl));
+        assert!(matches!(parse(&["a.sp"]).unwrap().command, Command::Run(file) if file == "a.sp"));
+        let run = parse(&["run", "a.sp"]).unwrap().command;
+        assert!(matches!(run, Command::Run(file) if file == "a.sp"));
+        let check = parse(&["check", "a.sp"]).unwrap().command;
+        assert!(matches!(check, Command::Check(file) if file == "a.sp"));
+        assert_eq!(parse(&["run"]).err().unwrap(), "Invalid arguments for `run`");
+        assert_eq!(parse(&["-x"]).err().unwrap(), "Invalid arguments");
+    }
+
+    #[test]
+    fn options_are_accepted_anywhere() {
+        let args = parse(&["run", "--tab-width", "4", "a.sp", "--max-recursion", "50"]).unwrap();
+        assert!(matches!(args.command, Command::Run(file) if file == "a.sp"));
+        assert_eq!((args.tab_width, args.max_call_depth), (4, 50));
+        assert!(parse(&["--tab-width", "0", "a.sp"]).is_err());
+        assert!(parse(&["--max-recursion"]).is_err());
+    }
+
+    #[test]
+    fn recursion_limit_is_capped_by_the_stack() {
+        let max = interpreter::MAX_CALL_DEPTH;
+        assert_eq!(parse(&["--max-recursion", &max.to_string()]).unwrap().max_call_depth, max);
+        assert_eq!(
+            parse(&["--max-recursion", &(max + 1).to_string()]).err().unwrap(),
+            format!("`--max-recursion` can be at most {}", max)
+        );
+        assert!(interpreter::stack_size(max) <= 256 * 1024 * 1024);
+        assert!(interpreter::DEFAULT_MAX_CALL_DEPTH <= max);
+        assert!(usage().contains(&format!("at most {}", max)));
+    }
+
+    #[test]
+    fn missing_file_is_an_io_error() {
+        let err = super::run_file("/nonexistent/script.sp", 8, 10).unwrap_err();
+        assert!(matches!(err.kind, crate::error::ErrorKind::IO));
+        assert!(err.message.starts_with("Couldn't read /nonexistent/script.sp: "));
     }
 }
@@ -1,11 +1,13 @@
-use crate::ast::AST;
-use crate::error::{eof_error, parser_error as error, Result};
+use crate::ast::{Comparison, Operator, AST};
+use crate::error::{eof_error, parser_error as error, Error, ErrorKind, Result};
 use crate::token::{Token, TokenKind};
 use std::rc::Rc;
 
 pub struct Parser {
     tokens: Vec<Token>,
     current_index: usize,
+    recover: bool,
+    errors: Vec<Error>,
 }
 
 impl Parser {
@@ -13,6 +15,8 @@ impl Parser {
         Parser {
             tokens,
             current_index: 0,
+            recover: false,
+            errors: vec![],
         }
     }
 
@@ -55,6 +59,47 @@ impl Parser {
         Ok(res)
     }
 
+    /// Like `parse`, but on a syntax error skips ahead to the next statement
+    /// and keeps going, so that every error in the file can be reported.
+    pub fn parse_with_recovery(&mut self) -> std::result::Result<Rc<AST>, Vec<Error>> {
+        self.recover = true;
+        let res = self.parse();
+        let mut errors = std::mem::take(&mut self.errors);
+        match res {
+            Ok(ast) if errors.is_empty() => Ok(ast),
+            Ok(_) => Err(errors),
+            Err(err) => {
+                errors.push(err);
+                Err(errors)
+            }
+        }
+    }
+
+    // Skip to the start of the next statement, keeping nested braces balanced
+    // so that we don't close the enclosing block early.
+    fn synchronize(&mut self, statement_start: usize) {
+        if self.current_index == statement_start {
+            self.increment();
+        }
+        let mut depth = 0;
+        loop {
+            let token = self.cur();
+            match token.kind {
+                TokenKind::EOF => break,
+                _ if depth == 0 && token.newline_before => break,
+                TokenKind::RightBrace if depth == 0 => break,
+                TokenKind::RightBrace => depth -= 1,
+                TokenKind::LeftBrace => depth += 1,
+                TokenKind::SemiColon if depth == 0 => {
+                    self.increment();
+                    break;
+                }
+                _ => {}
+            }
+            self.increment();
+        }
+    }
+
     fn parse_block(&mut self, global: bool) -> Result<Rc<AST>> {
         let mut span = self.cur().span;
         let mut statements = vec![];
@@ -71,7 +116,15 @@ impl Parser {
                 span = span.extend(&self.cur().span);
                 break;
             }
-            statements.push(self.parse_statement()?);
+            let statement_start = self.current_index;
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(err) if self.recover && !matches!(err.kind, ErrorKind::UnexpectedEOF) => {
+                    self.errors.push(err);
+                    self.synchronize(statement_start);
+                }
+                Err(err) => return Err(err),
+            }
         }
         Ok(Rc::new(AST::Block(span, statements)))
     }
@@ -82,7 +135,12 @@ impl Parser {
         }
         match self.cur().kind {
             TokenKind::SemiColon => self.increment(),
-            TokenKind::EOF => {}
+            TokenKind::EOF | TokenKind::RightBrace => {}
+            _ if self.floor_division_on_line() => error!(
+                self.cur().span,
+                "Expected line end, but got {:?} (`//` after code is floor division; use `#` for comments)",
+                self.cur().kind
+            ),
             _ => error!(
                 self.cur().span,
                 "Expected line end, but got {:?}",
@@ -92,20 +150,50 @@ impl Parser {
         Ok(())
     }
 
-    fn parse_lambda(&mut self) -> Result<Rc<AST>> {
-        let start = self.consume(TokenKind::Pipe)?.span;
+    // Whether a `//` appears earlier on the current line, most likely meant
+    // as a trailing comment.
+    fn floor_division_on_line(&self) -> bool {
+        for token in self.tokens[..self.current_index].iter().rev() {
+            if token.kind == TokenKind::SlashSlash {
+                return true;
+            }
+            if token.newline_before {
+                break;
+            }
+        }
+        false
+    }
+
+    /// Parses parameter names up to and including `end`. A final `*name`
+    /// collects any extra arguments.
+    fn parse_params(&mut self, end: TokenKind) -> Result<(Vec<String>, Option<String>)> {
         let mut args = vec![];
-        while self.cur().kind != TokenKind::Pipe {
-            args.push(self.consume(TokenKind::Identifier)?.text);
+        let mut variadic = None;
+        while self.cur().kind != end {
+            if variadic.is_some() {
+                error!(self.cur().span, "Variadic parameter must be the last parameter")
+            }
+            if self.cur().kind == TokenKind::Star {
+                self.increment();
+                variadic = Some(self.consume(TokenKind::Identifier)?.text);
+            } else {
+                args.push(self.consume(TokenKind::Identifier)?.text);
+            }
             if self.cur().kind == TokenKind::Comma {
                 self.increment();
             }
         }
         self.increment();
+        Ok((args, variadic))
+    }
+
+    fn parse_lambda(&mut self) -> Result<Rc<AST>> {
+        let start = self.consume(TokenKind::Pipe)?.span;
+        let (args, variadic) = self.parse_params(TokenKind::Pipe)?;
+        // A lambda is an expression, so whatever contains it ends the line
         let body = if self.cur().kind == TokenKind::FatArrow {
             self.increment();
             let expr = self.parse_expression()?;
-            self.consume_line_end()?;
             Rc::new(AST::Return(*expr.span(), expr))
         } else {
             self.parse_block(/*global*/ false)?
@@ -114,6 +202,7 @@ impl Parser {
             span: start.extend(body.span()),
             name: None,
             args,
+            variadic,
             body,
         }))
     }
@@ -122,14 +211,7 @@ impl Parser {
         let start = self.consume(TokenKind::Def)?.span;
         let name = self.consume(TokenKind::Identifier)?;
         self.consume(TokenKind::LeftParen)?;
-        let mut args = vec![];
-        while self.cur().kind != TokenKind::RightParen {
-            args.push(self.consume(TokenKind::Identifier)?.text);
-            if self.cur().kind == TokenKind::Comma {
-                self.increment();
-            }
-        }
-        self.increment();
+        let (args, variadic) = self.parse_params(TokenKind::RightParen)?;
         let body = if self.cur().kind == TokenKind::FatArrow {
             self.increment();
             let expr = self.parse_expression()?;
@@ -144,12 +226,120 @@ impl Parser {
                 span: start.extend(body.span()),
                 name: Some(name.text.clone()),
                 args,
+                variadic,
                 body,
             }),
             name.text,
         ))
     }
 
+    /// Parses from an `if` or `elif` keyword. `elif` is sugar for
+    /// `else if`, so both give the same tree.
+    fn parse_if(&mut self) -> Result<Rc<AST>> {
+        let span = self.cur().span;
+        self.increment();
+        let cond = self.parse_expression()?;
+        let body = self.parse_block(/*global*/ false)?;
+        let span = span.extend(body.span());
+        let else_body = match self.cur().kind {
+            TokenKind::Elif => self.parse_if()?,
+            TokenKind::Else => {
+                self.increment();
+                match self.cur().kind {
+                    TokenKind::If => self.parse_if()?,
+                    _ => self.parse_block(/*global*/ false)?,
+                }
+            }
+            _ => return Ok(Rc::new(AST::If(span, cond, body, None))),
+        };
+        Ok(Rc::new(AST::If(
+            span.extend(else_body.span()),
+            cond,
+            body,
+            Some(else_body),
+        )))
+    }
+
+    /// `match value { pattern => body, ..., else => body }`, where each body
+    /// is a block or an expression. An expression body must be followed by
+    /// `,` or the closing `}`, since it would otherwise run on into the next
+    /// arm (`-2 => ...` continues `print("one")` as a subtraction). The comma
+    /// after a block body is optional.
+    fn parse_match(&mut self) -> Result<Rc<AST>> {
+        let start = self.consume(TokenKind::Match)?.span;
+        let value = self.parse_expression()?;
+        self.consume(TokenKind::LeftBrace)?;
+        let mut arms = vec![];
+        let mut default = None;
+        while self.cur().kind != TokenKind::RightBrace {
+            if default.is_some() {
+                error!(self.cur().span, "The `else` arm must be the last one")
+            }
+            let pattern = match self.cur().kind {
+                TokenKind::Else => {
+                    self.increment();
+                    None
+                }
+                _ => Some(self.parse_pattern()?),
+            };
+            if self.cur().kind == TokenKind::Pipe {
+                error!(self.cur().span, "Match arms take a single pattern; use one arm per value")
+            }
+            self.consume(TokenKind::FatArrow)?;
+            let is_block = self.cur().kind == TokenKind::LeftBrace;
+            let body = match is_block {
+                true => self.parse_block(/*global*/ false)?,
+                false => self.parse_expression()?,
+            };
+            match pattern {
+                Some(pattern) => arms.push((pattern, body)),
+                None => default = Some(body),
+            }
+            match self.cur().kind {
+                TokenKind::Comma => self.increment(),
+                TokenKind::RightBrace => {}
+                _ if is_block => {}
+                _ => error!(
+                    self.cur().span,
+                    "Expected `,` or `}}` after a match arm, but got {:?}",
+                    self.cur().kind
+                ),
+            }
+        }
+        let end = self.consume(TokenKind::RightBrace)?.span;
+        Ok(Rc::new(AST::Match(start.extend(&end), value, arms, default)))
+    }
+
+    /// Match patterns are literals, optionally negated numbers.
+    fn parse_pattern(&mut self) -> Result<Rc<AST>> {
+        let negated = self.cur().kind == TokenKind::Minus;
+        let start = self.cur().span;
+        if negated {
+            self.increment();
+        }
+        let literal = match self.cur().kind {
+            TokenKind::IntegerLiteralBin
+            | TokenKind::IntegerLiteralOct
+            | TokenKind::IntegerLiteralDec
+            | TokenKind::IntegerLiteralHex
+            | TokenKind::FloatLiteral => self.parse_atom()?,
+            TokenKind::StringLiteral | TokenKind::True | TokenKind::False | TokenKind::Nothing
+                if !negated =>
+            {
+                self.parse_atom()?
+            }
+            _ => error!(
+                self.cur().span,
+                "Expected a literal pattern, but got {:?}",
+                self.cur().kind
+            ),
+        };
+        if negated {
+            return Ok(Rc::new(AST::Negate(start.extend(literal.span()), literal)));
+        }
+        Ok(literal)
+    }
+
     fn parse_statement(&mut self) -> Result<Rc<AST>> {
         match self.cur() {
             Token {
@@ -158,46 +348,37 @@ impl Parser {
                 ..
             } => {
                 self.increment();
-                let ident = self.consume(TokenKind::Identifier)?;
+                // `let [a, b] = ...` unpacks even a single name
+                let bracketed = self.cur().kind == TokenKind::LeftBracket;
+                if bracketed {
+                    self.increment();
+                }
+                let mut names = vec![self.consume(TokenKind::Identifier)?.text];
+                while self.cur().kind == TokenKind::Comma {
+                    self.increment();
+                    names.push(self.consume(TokenKind::Identifier)?.text);
+                }
+                if bracketed {
+                    self.consume(TokenKind::RightBracket)?;
+                }
                 self.consume(TokenKind::Equals)?;
-                let expr = self.parse_expression()?;
+                let mut values = self.parse_expression_list()?;
                 self.consume_line_end()?;
-                Ok(Rc::new(AST::VarDeclaration(
-                    span.extend(expr.span()),
-                    ident.text,
-                    expr,
-                )))
+                let span = span.extend(values.last().unwrap().span());
+                if names.len() == 1 && values.len() == 1 && !bracketed {
+                    Ok(Rc::new(AST::VarDeclaration(span, names.remove(0), values.remove(0))))
+                } else {
+                    Ok(Rc::new(AST::MultiDeclaration(span, names, values)))
+                }
             }
             Token {
                 kind: TokenKind::If,
-                span,
                 ..
-            } => {
-                self.increment();
-                let cond = self.parse_expression()?;
-                let body = self.parse_block(/*global*/ false)?;
-                let span = span.extend(body.span());
-                match self.cur() {
-                    Token {
-                        kind: TokenKind::Else,
-                        span,
-                        ..
-                    } => {
-                        self.increment();
-                        let else_body = match self.cur().kind {
-                            TokenKind::If => self.parse_statement()?,
-                            _ => self.parse_block(/*global*/ false)?,
-                        };
-                        Ok(Rc::new(AST::If(
-                            span.extend(else_body.span()),
-                            cond,
-                            body,
-                            Some(else_body),
-                        )))
-                    }
-                    _ => Ok(Rc::new(AST::If(span, cond, body, None))),
-                }
-            }
+            } => self.parse_if(),
+            Token {
+                kind: TokenKind::Match,
+                ..
+            } => self.parse_match(),
             Token {
                 kind: TokenKind::Def,
                 ..
@@ -215,7 +396,7 @@ impl Parser {
                 Ok(Rc::new(AST::Assignment(
                     span.extend(deco.span()),
                     Rc::new(AST::Variable(span.extend(deco.span()), name)),
-                    Rc::new(AST::Call(span.extend(deco.span()), deco, vec![func])),
+                    Rc::new(AST::Call(span.extend(deco.span()), deco, vec![func], vec![])),
                 )))
             }
             Token {
@@ -311,6 +492,16 @@ impl Parser {
                 self.consume_line_end()?;
                 Ok(Rc::new(AST::Return(span.extend(expr.span()), expr)))
             }
+            Token {
+                kind: TokenKind::Del,
+                span,
+                ..
+            } => {
+                self.increment();
+                let target = self.parse_postfix()?;
+                self.consume_line_end()?;
+                Ok(Rc::new(AST::Del(span.extend(target.span()), target)))
+            }
             Token {
                 kind: TokenKind::Assert,
                 span,
@@ -318,19 +509,32 @@ impl Parser {
             } => {
                 self.increment();
                 let cond = self.parse_expression()?;
-                let span = span.extend(cond.span());
+                let mut span = span.extend(cond.span());
+                let mut message = None;
                 if self.cur().kind == TokenKind::Comma {
                     self.increment();
-                    span.extend(&self.cur().span);
-                    self.consume(TokenKind::StringLiteral)?;
+                    span = span.extend(&self.cur().span);
+                    message = Some(self.consume(TokenKind::StringLiteral)?.text);
                 }
                 self.consume_line_end()?;
-                Ok(Rc::new(AST::Assert(span, cond)))
+                Ok(Rc::new(AST::Assert(span, cond, message)))
             }
             _ => {
-                let expr = self.parse_expression();
+                let expr = self.parse_expression()?;
+                if self.cur().kind == TokenKind::Comma {
+                    let mut targets = vec![expr];
+                    while self.cur().kind == TokenKind::Comma {
+                        self.increment();
+                        targets.push(self.parse_logical_or()?);
+                    }
+                    self.consume(TokenKind::Equals)?;
+                    let values = self.parse_expression_list()?;
+                    self.consume_line_end()?;
+                    let span = targets[0].span().extend(values.last().unwrap().span());
+                    return Ok(Rc::new(AST::MultiAssignment(span, targets, values)));
+                }
                 self.consume_line_end()?;
-                expr
+                Ok(expr)
             }
         }
     }
@@ -339,72 +543,57 @@ impl Parser {
         self.parse_assignment()
     }
 
+    fn parse_expression_list(&mut self) -> Result<Vec<Rc<AST>>> {
+        let mut exprs = vec![self.parse_expression()?];
+        while self.cur().kind == TokenKind::Comma {
+            self.increment();
+            exprs.push(self.parse_expression()?);
+        }
+        Ok(exprs)
+    }
+
     fn parse_assignment(&mut self) -> Result<Rc<AST>> {
-        let left = self.parse_comparison()?;
+        let left = self.parse_logical_or()?;
         match self.cur() {
             Token {
                 kind: TokenKind::Equals,
                 ..
             } => {
                 self.increment();
-                let right = self.parse_comparison()?;
+                let right = self.parse_logical_or()?;
                 Ok(Rc::new(AST::Assignment(
                     left.span().extend(right.span()),
                     left,
                     right,
                 )))
             }
-            _ => Ok(left),
-        }
-    }
-
-    fn parse_comparison(&mut self) -> Result<Rc<AST>> {
-        let mut left = self.parse_logical_or()?;
-        while let Token {
-            kind:
-                TokenKind::EqualsEquals
-                | TokenKind::BangEquals
-                | TokenKind::LessThan
-                | TokenKind::GreaterThan
-                | TokenKind::LessEquals
-                | TokenKind::GreaterEquals,
-            ..
-        } = self.cur()
-        {
-            let op = self.cur().kind;
-            self.increment();
-            let right = self.parse_logical_or()?;
-            left = match op {
-                TokenKind::EqualsEquals => {
-                    Rc::new(AST::Equals(left.span().extend(right.span()), left, right))
-                }
-                TokenKind::BangEquals => Rc::new(AST::NotEquals(
-                    left.span().extend(right.span()),
-                    left,
-                    right,
-                )),
-                TokenKind::LessThan => {
-                    Rc::new(AST::LessThan(left.span().extend(right.span()), left, right))
-                }
-                TokenKind::GreaterThan => Rc::new(AST::GreaterThan(
-                    left.span().extend(right.span()),
-                    left,
-                    right,
-                )),
-                TokenKind::LessEquals => Rc::new(AST::LessEquals(
-                    left.span().extend(right.span()),
-                    left,
-                    right,
-                )),
-                TokenKind::GreaterEquals => Rc::new(AST::GreaterEquals(
+            Token {
+                kind:
+                    kind @ (TokenKind::PlusEquals
+                    | TokenKind::MinusEquals
+                    | TokenKind::StarEquals
+                    | TokenKind::SlashEquals
+                    | TokenKind::PercentEquals),
+                ..
+            } => {
+                let op = match kind {
+                    TokenKind::PlusEquals => Operator::Plus,
+                    TokenKind::MinusEquals => Operator::Minus,
+                    TokenKind::StarEquals => Operator::Multiply,
+                    TokenKind::SlashEquals => Operator::Divide,
+                    _ => Operator::Modulo,
+                };
+                self.increment();
+                let right = self.parse_logical_or()?;
+                Ok(Rc::new(AST::CompoundAssignment(
                     left.span().extend(right.span()),
+                    op,
                     left,
                     right,
-                )),
-                _ => unreachable!(),
+                )))
             }
+            _ => Ok(left),
         }
-        Ok(left)
     }
 
     fn parse_logical_or(&mut self) -> Result<Rc<AST>> {
@@ -422,19 +611,116 @@ impl Parser {
     }
 
     fn parse_logical_and(&mut self) -> Result<Rc<AST>> {
-        let mut left = self.parse_additive()?;
+        let mut left = self.parse_comparison()?;
         while let Token {
             kind: TokenKind::And,
             ..
         } = self.cur()
         {
             self.increment();
-            let right = self.parse_additive()?;
+            let right = self.parse_comparison()?;
             left = Rc::new(AST::And(left.span().extend(right.span()), left, right));
         }
         Ok(left)
     }
 
+    fn parse_comparison(&mut self) -> Result<Rc<AST>> {
+        let first = self.parse_bitwise_or()?;
+        let mut rest = vec![];
+        loop {
+            let op = match self.cur().kind {
+                TokenKind::EqualsEquals => Comparison::Equals,
+                TokenKind::BangEquals => Comparison::NotEquals,
+                TokenKind::LessThan => Comparison::LessThan,
+                TokenKind::GreaterThan => Comparison::GreaterThan,
+                TokenKind::LessEquals => Comparison::LessEquals,
+                TokenKind::GreaterEquals => Comparison::GreaterEquals,
+                TokenKind::In => Comparison::In,
+                // `not` is only a comparison as part of `not in`
+                TokenKind::Not if self.tokens[self.current_index + 1].kind == TokenKind::In => {
+                    self.increment();
+                    Comparison::NotIn
+                }
+                _ => break,
+            };
+            self.increment();
+            rest.push((op, self.parse_bitwise_or()?));
+        }
+
+        let Some((op, right)) = rest.last().cloned() else {
+            return Ok(first);
+        };
+        let span = first.span().extend(right.span());
+        if rest.len() > 1 {
+            return Ok(Rc::new(AST::ChainedComparison(span, first, rest)));
+        }
+        let left = first;
+        Ok(Rc::new(match op {
+            Comparison::Equals => AST::Equals(span, left, right),
+            Comparison::NotEquals => AST::NotEquals(span, left, right),
+            Comparison::LessThan => AST::LessThan(span, left, right),
+            Comparison::GreaterThan => AST::GreaterThan(span, left, right),
+            Comparison::LessEquals => AST::LessEquals(span, left, right),
+            Comparison::GreaterEquals => AST::GreaterEquals(span, left, right),
+            Comparison::In => AST::In(span, left, right),
+            Comparison::NotIn => AST::NotIn(span, left, right),
+        }))
+    }
+
+    // Bitwise operators bind tighter than comparisons but looser than
+    // arithmetic, as in Python: `x & 1 == 0` is `(x & 1) == 0`, and
+    // `1 << n - 1` is `1 << (n - 1)`. From loosest: `|`, `^`, `&`, shifts.
+
+    fn parse_bitwise_or(&mut self) -> Result<Rc<AST>> {
+        let mut left = self.parse_bitwise_xor()?;
+        while self.cur().kind == TokenKind::Pipe {
+            self.increment();
+            let right = self.parse_bitwise_xor()?;
+            left = Rc::new(AST::BitOr(left.span().extend(right.span()), left, right));
+        }
+        Ok(left)
+    }
+
+    fn parse_bitwise_xor(&mut self) -> Result<Rc<AST>> {
+        let mut left = self.parse_bitwise_and()?;
+        while self.cur().kind == TokenKind::Caret {
+            self.increment();
+            let right = self.parse_bitwise_and()?;
+            left = Rc::new(AST::BitXor(left.span().extend(right.span()), left, right));
+        }
+        Ok(left)
+    }
+
+    fn parse_bitwise_and(&mut self) -> Result<Rc<AST>> {
+        let mut left = self.parse_shift()?;
+        while self.cur().kind == TokenKind::Ampersand {
+            self.increment();
+            let right = self.parse_shift()?;
+            left = Rc::new(AST::BitAnd(left.span().extend(right.span()), left, right));
+        }
+        Ok(left)
+    }
+
+    fn parse_shift(&mut self) -> Result<Rc<AST>> {
+        let mut left = self.parse_additive()?;
+        while let Token {
+            kind: TokenKind::LessLess | TokenKind::GreaterGreater,
+            ..
+        } = self.cur()
+        {
+            let op = self.cur().kind;
+            self.increment();
+            let right = self.parse_additive()?;
+            let span = left.span().extend(right.span());
+            left = match op {
+                TokenKind::LessLess => Rc::new(AST::ShiftLeft(span, left, right)),
+                TokenKind::GreaterGreater => Rc::new(AST::ShiftRight(span, left, right)),
+                _ => unreachable!(),
+            }
+        }
+        Ok(left)
+    }
+
     fn parse_additive(&mut self) -> Result<Rc<AST>> {
         let mut left = self.parse_multiplicative()?;
         while let Token {
@@ -462,7 +748,7 @@ impl Parser {
         let mut left = self.parse_prefix()?;
 
         while let Token {
-            kind: TokenKind::Star | TokenKind::Slash,
+            kind: TokenKind::Star | TokenKind::Slash | TokenKind::SlashSlash | TokenKind::Percent,
             ..
         } = self.cur()
         {
@@ -476,6 +762,12 @@ impl Parser {
                 TokenKind::Slash => {
                     Rc::new(AST::Divide(left.span().extend(right.span()), left, right))
                 }
+                TokenKind::SlashSlash => {
+                    Rc::new(AST::FloorDivide(left.span().extend(right.span()), left, right))
+                }
+                TokenKind::Percent => {
+                    Rc::new(AST::Modulo(left.span().extend(right.span()), left, right))
+                }
                 _ => unreachable!(),
             }
         }
@@ -497,6 +789,18 @@ impl Parser {
                 let expr = self.parse_prefix()?;
                 Ok(Rc::new(AST::Not(start.extend(expr.span()), expr)))
             }
+            TokenKind::Minus => {
+                let start = self.cur().span;
+                self.increment();
+                let expr = self.parse_prefix()?;
+                Ok(Rc::new(AST::Negate(start.extend(expr.span()), expr)))
+            }
+            TokenKind::Tilde => {
+                let start = self.cur().span;
+                self.increment();
+                let expr = self.parse_prefix()?;
+                Ok(Rc::new(AST::BitNot(start.extend(expr.span()), expr)))
+            }
             TokenKind::PlusPlus | TokenKind::MinusMinus => {
                 let offset = if self.cur().kind == TokenKind::PlusPlus { 1 } else { -1 };
                 let start = self.cur().span;
@@ -504,10 +808,26 @@ impl Parser {
                 let expr = self.parse_prefix()?;
                 Ok(Rc::new(AST::PreIncrement(start.extend(expr.span()), expr, offset)))
             }
-            _ => self.parse_postfix(),
+            _ => self.parse_power(),
         }
     }
 
+    fn parse_power(&mut self) -> Result<Rc<AST>> {
+        let base = self.parse_postfix()?;
+        if self.cur().kind != TokenKind::StarStar {
+            return Ok(base);
+        }
+        self.increment();
+        // Going back through `parse_prefix` makes `**` right-associative,
+        // so `2 ** 3 ** 2` is `2 ** (3 ** 2)`
+        let exponent = self.parse_prefix()?;
+        Ok(Rc::new(AST::Power(
+            base.span().extend(exponent.span()),
+            base,
+            exponent,
+        )))
+    }
+
     fn parse_postfix(&mut self) -> Result<Rc<AST>> {
         let mut val = self.parse_atom()?;
         loop {
@@ -565,6 +885,7 @@ impl Parser {
                 } => {
                     self.increment();
                     let mut args = vec![];
+                    let mut kwargs = vec![];
                     let mut span = *val.span();
                     loop {
                         match self.cur().kind {
@@ -574,7 +895,20 @@ impl Parser {
                                 break;
                             }
                             _ => {
-                                args.push(self.parse_expression()?);
+                                let is_keyword = self.cur().kind == TokenKind::Identifier
+                                    && self.tokens[self.current_index + 1].kind == TokenKind::Equals;
+                                if is_keyword {
+                                    let name = self.consume(TokenKind::Identifier)?.text;
+                                    self.increment();
+                                    kwargs.push((name, self.parse_expression()?));
+                                } else if !kwargs.is_empty() {
+                                    error!(
+                                        self.cur().span,
+                                        "Positional arguments must come before keyword arguments"
+                                    )
+                                } else {
+                                    args.push(self.parse_expression()?);
+                                }
                                 match self.cur().kind {
                                     TokenKind::Comma => self.increment(),
                                     TokenKind::RightParen => {}
@@ -591,7 +925,15 @@ impl Parser {
                             }
                         }
                     }
-                    val = Rc::new(AST::Call(span, val, args));
+                    val = Rc::new(AST::Call(span, val, args, kwargs));
+                }
+                Token {
+                    kind: TokenKind::Dot,
+                    ..
+                } => {
+                    self.increment();
+                    let name = self.consume(TokenKind::Identifier)?;
+                    val = Rc::new(AST::Member(val.span().extend(&name.span), val, name.text));
                 }
                 Token {
                     kind: TokenKind::DotDot,
@@ -735,6 +1077,34 @@ impl Parser {
                 self.increment();
                 Ok(Rc::new(AST::StringLiteral(span, text)))
             }
+            Token {
+                kind: TokenKind::FormatStringStart,
+                span,
+                ..
+            } => {
+                self.increment();
+                let mut parts = vec![];
+                // The lexer alternates literal text with `{ expr }` groups
+                loop {
+                    let token = self.cur();
+                    match token.kind {
+                        TokenKind::StringLiteral => {
+                            self.increment();
+                            if !token.text.is_empty() {
+                                parts.push(Rc::new(AST::StringLiteral(token.span, token.text)));
+                            }
+                        }
+                        TokenKind::LeftBrace => {
+                            self.increment();
+                            parts.push(self.parse_expression()?);
+                            self.consume(TokenKind::RightBrace)?;
+                        }
+                        _ => break,
+                    }
+                }
+                let end = self.consume(TokenKind::FormatStringEnd)?.span;
+                Ok(Rc::new(AST::FormatString(span.extend(&end), parts)))
+            }
             Token {
                 kind: TokenKind::Identifier,
                 span,
@@ -781,3 +1151,113 @@ impl Parser {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Parser;
+    use crate::ast::AST;
+    use crate::error::Result;
+    use crate::lexer::Lexer;
+    use std::rc::Rc;
+
+    fn parse(source: &str) -> Result<Rc<AST>> {
+        let tokens = Lexer::new(source.to_string(), "<test>").lex()?;
+        Parser::new(tokens).parse()
+    }
+
+    fn error(source: &str) -> String {
+        match parse(source) {
+            Ok(ast) => panic!("expected an error, got {:?}", ast),
+            Err(err) => err.message,
+        }
+    }
+
+    #[test]
+    fn closing_brace_ends_a_statement() {
+        assert!(parse("if x { print(x) }").is_ok());
+        assert!(parse("def f(x) { x + 1 }\nwhile x { x = x - 1 }").is_ok());
+        assert_eq!(
+            error("if x { print(x) print(x) }"),
+            "Expected line end, but got Identifier"
+        );
+    }
+
+    #[test]
+    fn trailing_slash_slash_hints_at_hash_comments() {
+        assert!(parse("let x = 7 // 2\n# a comment\nx").is_ok());
+        assert_eq!(
+            error("let x = 7 // seven days"),
+            "Expected line end, but got Identifier (`//` after code is floor division; use `#` for comments)"
+        );
+        assert_eq!(error("let x = 7 days"), "Expected line end, but got Identifier");
+    }
+    #[test]
+    fn match_patterns_are_literals() {
+        let patterns = "1 => 2, -3 => 4, 0x1F => 5, \"a\" => 6, true => 7, nothing => 8";
+        assert!(parse(&format!("match x {{ {}, else => 9 }}", patterns)).is_ok());
+        assert_eq!(
+            error("match x { 1 | 2 => 3 }"),
+            "Match arms take a single pattern; use one arm per value"
+        );
+        assert_eq!(
+            error("match x { y => 3 }"),
+            "Expected a literal pattern, but got Identifier"
+        );
+        assert_eq!(
+            error("match x { 1 + 1 => 3 }"),
+            "Expected token FatArrow, but got Plus"
+        );
+    }
+    #[test]
+    fn recovery_reports_every_syntax_error() {
+        let tokens = Lexer::new("let x = )\nlet y = 1\nlet = 2\n".to_string(), "<test>")
+            .lex()
+            .unwrap();
+        let errors = Parser::new(tokens).parse_with_recovery().unwrap_err();
+        let lines: Vec<_> = errors.iter().map(|err| err.span.0.line).collect();
+        assert_eq!(lines, vec![1, 3]);
+    }
+
+    #[test]
+    fn chained_member_access_nests() {
+        let ast = parse("a.b.c").unwrap();
+        let AST::Block(_, statements) = &*ast else { panic!("expected a block") };
+        let AST::Member(_, inner, c) = &*statements[0] else { panic!("expected a member") };
+        let AST::Member(_, a, b) = &**inner else { panic!("expected a nested member") };
+        assert!(matches!(&**a, AST::Variable(_, name) if name == "a"));
+        assert_eq!((b.as_str(), c.as_str()), ("b", "c"));
+    }
+
+    // Spans aside, the structure of blocks and `if`s
+    fn shape(ast: &AST) -> String {
+        match ast {
+            AST::Block(_, statements) => {
+                let statements: Vec<_> = statements.iter().map(|s| shape(s)).collect();
+                format!("{{{}}}", statements.join("; "))
+            }
+            AST::If(_, cond, body, else_body) => {
+                let else_body = else_body.as_ref().map_or("-".to_string(), |e| shape(e));
+                format!("if {} {} else {}", cond, shape(body), else_body)
+            }
+            other => other.to_string(),
+        }
+    }
+
+    #[test]
+    fn elif_is_else_if() {
+        let elif = parse("if a { 1 } elif b { 2 } elif c { 3 } else { 4 }").unwrap();
+        let else_if = parse("if a { 1 } else if b { 2 } else if c { 3 } else { 4 }").unwrap();
+        assert_eq!(shape(&elif), shape(&else_if));
+        assert_eq!(shape(&elif), "{if a {1} else if b {2} else if c {3} else {4}}");
+    }
+
+        #[test]
+        fn match_expression_arms_need_a_comma() {
+            assert!(parse("match x {\n    1 => { \"one\" }\n    -2 => { \"neg\" }\n}").is_ok());
+            assert!(parse("match x {\n    1 => \"one\",\n    -2 => \"neg\"\n}").is_ok());
+            assert_eq!(
+                error("match x {\n    1 => \"one\"\n    \"a\" => \"a\"\n}"),
+                "Expected `,` or `}` after a match arm, but got StringLiteral"
+            );
+        }
+}
@@ -1,59 +1,144 @@
 use crate::ast::AST;
-use crate::common::Ref;
+use crate::common::{Ref, DEFAULT_TAB_WIDTH};
 use crate::error::{Error, Result, ErrorKind};
 use crate::interpreter::value::Value;
 use crate::interpreter::{Interpreter, Scope};
-use std::io::Write;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::path::PathBuf;
 use std::rc::Rc;
 
+const HELP: &str = "\
+Commands:
+    :load <file>    run a file, keeping its definitions in this session
+    :help           show this message
+    :quit           leave the REPL (Ctrl-D works too)
+
+The last result is available as `_`.";
+
 pub struct Repl {
     interpreter: Interpreter,
     global_scope: Ref<Scope>,
+    editor: DefaultEditor,
+    history_file: Option<PathBuf>,
 }
 
 impl Repl {
     pub fn new() -> Repl {
         let interpreter = Interpreter::new();
         let global_scope = Scope::new(None, false);
+        let mut editor = DefaultEditor::new().expect("Failed to set up line editor");
+        let history_file = std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".serpens_history"));
+        if let Some(file) = &history_file {
+            // There is no history the first time around
+            let _ = editor.load_history(file);
+        }
         Repl {
             interpreter,
             global_scope,
+            editor,
+            history_file,
         }
     }
 
-    fn run_once(&mut self) -> Result<()> {
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.interpreter.set_max_call_depth(depth);
+    }
+
+    /// Reads and runs one (possibly multi-line) input. Returns `false` once
+    /// the user is done, i.e. on end of input.
+    fn run_once(&mut self) -> Result<bool> {
         let mut input = String::new();
         let ast = loop {
-            let mut temp = String::new();
-            print!("{}", if input.is_empty() { ">>> " } else { "... " });
-            std::io::stdout().flush().expect("Failed to flush stdout");
-            std::io::stdin()
-                .read_line(&mut temp)
-                .expect("Failed to read line");
-            if temp.trim().is_empty() {
+            let prompt = if input.is_empty() {
+                ">>> ".to_string()
+            } else {
+                format!("... {}", "    ".repeat(open_depth(&input)))
+            };
+            let line = match self.editor.readline(&prompt) {
+                Ok(line) => line,
+                // Ctrl-C discards the current input
+                Err(ReadlineError::Interrupted) => return Ok(true),
+                Err(ReadlineError::Eof) => return Ok(false),
+                Err(err) => panic!("Failed to read line: {}", err),
+            };
+            if line.trim().is_empty() {
                 if input.trim().is_empty() {
-                    return Ok(());
+                    return Ok(true);
                 }
                 continue;
             }
+            self.add_history(&line);
 
-            input.push_str(&temp);
+            if input.is_empty() && line.trim_start().starts_with(':') {
+                return self.run_command(line.trim());
+            }
+
+            input.push_str(&line);
+            input.push('\n');
             match self.try_parse(input.clone()) {
                 Ok(ast) => break ast,
                 Err(Error{kind: ErrorKind::UnexpectedEOF, ..}) => {}
                 Err(err) => return Err(err),
             }
         };
+        let val = self.evaluate(&ast)?;
+        if !matches!(val, Value::Nothing) {
+            println!("{}", val.repr());
+        }
+        Ok(true)
+    }
+
+    /// Runs one input in the session's global scope.
+    fn evaluate(&mut self, ast: &Rc<AST>) -> Result<Value> {
         let val = self
             .interpreter
-            .run_block_without_new_scope(&ast, self.global_scope.clone())?;
-        match &val {
-            Value::Nothing => {}
-            _ => println!("{}", val.repr()),
+            .run_block_without_new_scope(ast, self.global_scope.clone())?;
+        if !matches!(val, Value::Nothing) {
+            // `_` refers to the last result, i.e. `1 + 1` then `_ * 2` gives 4
+            self.global_scope
+                .borrow_mut()
+                .vars
+                .insert("_".to_string(), val.clone());
+        }
+        Ok(val)
+    }
+
+    fn run_command(&mut self, command: &str) -> Result<bool> {
+        match command.split_once(char::is_whitespace) {
+            Some((":load", filename)) => match self.load(filename.trim()) {
+                Err(err) if !matches!(err.kind, ErrorKind::Abort) => crate::report(&err, DEFAULT_TAB_WIDTH),
+                res => res?,
+            },
+            None if command == ":load" => println!("Usage: :load <file>"),
+            None if command == ":help" => println!("{}", HELP),
+            None if command == ":quit" => return Ok(false),
+            _ => println!("Unknown command `{}`, try :help", command),
         }
+        Ok(true)
+    }
+
+    /// Runs a file in the session's global scope. Its errors are reported
+    /// against the file's own source rather than the REPL input.
+    fn load(&mut self, filename: &str) -> Result<()> {
+        let filename: &'static str = Box::leak(filename.to_string().into_boxed_str());
+        let content = crate::read_source(filename)?;
+        let tokens = crate::lexer::Lexer::new(content, filename).lex()?;
+        let ast = crate::parser::Parser::new(tokens).parse()?;
+        self.interpreter
+            .run_block_without_new_scope(&ast, self.global_scope.clone())?;
         Ok(())
     }
 
+    fn add_history(&mut self, line: &str) {
+        // History is a convenience, so failing to save it isn't reported
+        let _ = self.editor.add_history_entry(line);
+        if let Some(file) = &self.history_file {
+            let _ = self.editor.save_history(file);
+        }
+    }
+
     fn try_parse(&self, input: String) -> Result<Rc<AST>> {
         let mut lex = crate::lexer::Lexer::new(input, "<repl>");
         let tokens = lex.lex()?;
@@ -64,7 +149,12 @@ impl Repl {
     pub fn run(&mut self) {
         loop {
             match self.run_once() {
-                Ok(_) => {}
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(err @ Error { kind: ErrorKind::Abort, .. }) => {
+                    err.print_abort();
+                    std::process::exit(1);
+                }
                 Err(err) => {
                     if err.span.0.line == err.span.1.line {
                         let len = err.span.1.column - err.span.0.column;
@@ -75,8 +165,57 @@ impl Repl {
                         }
                     }
                     println!("\x1b[0;31m{}\x1b[0m", err);
+                    print!("{}", err.format_trace());
                 }
             }
         }
     }
 }
+
+/// How many brackets are still open in `input`, used to indent
+/// continuation lines. Brackets inside string literals are skipped.
+fn open_depth(input: &str) -> usize {
+    let mut depth: usize = 0;
+    let mut in_string = false;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if in_string => {
+                chars.next();
+            }
+            '"' => in_string = !in_string,
+            '{' | '[' | '(' if !in_string => depth += 1,
+            '}' | ']' | ')' if !in_string => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    depth
+}
+
+#[cfg(test)]
+mod tests {
+    use super::open_depth;
+
+    #[test]
+    fn continuation_indent_follows_open_brackets() {
+        assert_eq!(open_depth("let x = 1\n"), 0);
+        assert_eq!(open_depth("def f() {\n"), 1);
+        assert_eq!(open_depth("def f() {\n    for x in [1,\n"), 2);
+        assert_eq!(open_depth("def f() {\n    if x {\n    }\n"), 1);
+        assert_eq!(open_depth("print(\"{ [ (\", \"\\\"{\"\n"), 1);
+    }
+
+    #[test]
+    fn underscore_is_the_last_result() {
+        let mut repl = super::Repl::new();
+        let mut eval = |input: &str| {
+            let ast = repl.try_parse(input.to_string()).unwrap();
+            repl.evaluate(&ast).unwrap().repr()
+        };
+        assert_eq!(eval("1 + 1"), "2");
+        assert_eq!(eval("_ * 2"), "4");
+        // Results of `nothing` don't replace it
+        assert_eq!(eval("nothing"), "nothing");
+        assert_eq!(eval("_ + 1"), "5");
+    }
+}
@@ -4,6 +4,7 @@ use crate::common::Span;
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum TokenKind {
+    Ampersand,
     And,
     Assert,
     At,
@@ -12,15 +13,19 @@ pub enum TokenKind {
     Colon,
     Comma,
     Def,
+    Del,
     Dot,
     DotDot,
     EOF,
+    Elif,
     Else,
     Equals,
     EqualsEquals,
     False,
     FatArrow,
     FloatLiteral,
+    FormatStringEnd,
+    FormatStringStart,
     For,
     Identifier,
     If,
@@ -35,28 +40,41 @@ pub enum TokenKind {
     LessThan,
     LessEquals,
     Let,
+    Match,
     Minus,
     MinusMinus,
+    MinusEquals,
     Not,
     GreaterThan,
     GreaterEquals,
     Nothing,
     Or,
+    Percent,
+    PercentEquals,
     Pipe,
     Plus,
     PlusPlus,
+    PlusEquals,
     Return,
     RightBrace,
     RightBracket,
     RightParen,
     SemiColon,
     Slash,
+    SlashEquals,
+    SlashSlash,
     Star,
+    StarStar,
+    StarEquals,
     StringLiteral,
     True,
     While,
     Continue,
     Break,
+    Caret,
+    GreaterGreater,
+    LessLess,
+    Tilde,
 }
 
 #[derive(Debug, Clone)]
@@ -83,10 +101,13 @@ impl Token {
                 "and" => TokenKind::And,
                 "assert" => TokenKind::Assert,
                 "def" => TokenKind::Def,
+                "del" => TokenKind::Del,
+                "elif" => TokenKind::Elif,
                 "else" => TokenKind::Else,
                 "false" => TokenKind::False,
                 "if" => TokenKind::If,
                 "let" => TokenKind::Let,
+                "match" => TokenKind::Match,
                 "not" => TokenKind::Not,
                 "nothing" => TokenKind::Nothing,
                 "or" => TokenKind::Or,